@@ -4,7 +4,6 @@ type ParserItem = Result<(usize, Token, usize), crate::parser::ParserError>;
 
 pub fn token_iter<'input>(input: &'input str) -> impl Iterator<Item=ParserItem> + 'input {
     Token::lexer(input)
-        .filter(|tok| tok.value != Token::Whitespace)
         .map(|tok| match tok {
             Spanned { value: Token::Error, .. } =>
                 Err(crate::parser::ParserError),
@@ -14,6 +13,7 @@ pub fn token_iter<'input>(input: &'input str) -> impl Iterator<Item=ParserItem>
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip("[ \t\r\n]+")]
 pub enum Token {
     #[regex("[a-zA-Z_][a-zA-Z0-9_]*")]
     Ident,
@@ -38,9 +38,6 @@ pub enum Token {
     #[token("/")]
     Div,
 
-    #[regex("[ \t\r\n]+")]
-    Whitespace,
-
     #[error]
     Error,
 }
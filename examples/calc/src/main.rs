@@ -22,8 +22,8 @@ impl Expr {
         let res = match self {
             Expr::Call { name, args } =>
                 match name.as_str() {
-                    "sin" => eval_sine(&args)?,
-                    "cos" => eval_cosine(&args)?,
+                    "sin" => eval_sine(args)?,
+                    "cos" => eval_cosine(args)?,
                     _ => return Err(())
                 },
             Expr::UMinus(e) =>
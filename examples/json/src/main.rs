@@ -1,10 +1,9 @@
 use sana::Sana;
-
-use std::io::Read;
+use sana::stream::StreamLexer;
 
 #[derive(Debug, Clone, Copy, PartialEq, Sana)]
 enum Token {
-    #[regex("\"" . r#"([^"\\]|\\["\\/bfnrt]|\\u\d\d\d\d)*"# . "\"")]
+    #[regex("\"" . r#"([^"\\]|\\["\\/bfnrt]|\\u[0-9a-fA-F]{4})*"# . "\"")]
     String,
     #[regex(r"-?(0|[1-9]\d*)(\.\d+)?([eE][+-]?\d+)?")]
     Number,
@@ -38,13 +37,11 @@ enum Token {
 }
 
 fn main() {
-    let mut input = String::new();
-    std::io::stdin()
-        .read_to_string(&mut input).unwrap();
-
-    let lexer = Token::lexer(&input);
+    let stdin = std::io::stdin();
+    let lexer = StreamLexer::<_, Token>::new(stdin.lock());
 
     for tok in lexer {
+        let tok = tok.unwrap();
         println!("{:?} at {}..{}", tok.value, tok.start, tok.end);
 
         if tok.value == Token::Error { break }
@@ -4,7 +4,7 @@ use arbitrary::{Arbitrary, Unstructured};
 
 use sana_core::{Rule, RuleSet};
 use sana_core::regex::Regex;
-use sana_core::ir::Ir;
+use sana_core::ir::{Ir, Passes};
 use std::convert::TryFrom;
 
 #[derive(Clone, Debug)]
@@ -40,6 +40,7 @@ fuzz_target!(|rules: Vec<(String, Action, Priority)>| {
     let dfa = ruleset.construct_dfa();
 
     if let Ok(dfa) = dfa {
-        let _ = Ir::from_automata(dfa);
+        let mut ir = Ir::from_automata(dfa);
+        ir.optimize(Passes::ALL);
     }
 });
@@ -64,6 +64,7 @@ enum Token {
     FatArrow,
 
     #[error]
+    #[allow(clippy::enum_variant_names)]
     InvalidToken,
 }
 
@@ -0,0 +1,208 @@
+//! Lexers constructed at runtime
+//!
+//! A `DynamicSana` is built from a `RuleSet` or from a list of patterns and
+//! owns the code of the lexer, so no `#[derive(Sana)]` is required.
+//!
+//! ```rust
+//! use sana::Spanned;
+//! use sana::dynamic::DynamicSana;
+//!
+//! let sana = DynamicSana::from_patterns(&[
+//!     ("[a-z]+", "ident"),
+//!     ("[0-9]+", "number"),
+//!     (" ", "space"),
+//! ], "error").unwrap();
+//!
+//! let mut lexer = sana.lexer("x 42");
+//!
+//! assert_eq!(lexer.next(), Some(Spanned { value: "ident", start: 0, end: 1 }));
+//! assert_eq!(lexer.next(), Some(Spanned { value: "space", start: 1, end: 2 }));
+//! assert_eq!(lexer.next(), Some(Spanned { value: "number", start: 2, end: 4 }));
+//! assert_eq!(lexer.next(), None);
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+
+pub use sana_core::{Rule, RuleSet};
+pub use sana_core::regex::Regex;
+
+use sana_core::ir::{cut_trailing, Ir, Op, Passes, Vm};
+
+use crate::Spanned;
+
+/// An error of the lexer construction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The pattern with the given index is invalid
+    Pattern(usize, String),
+    /// The rule with the given index matches the empty string
+    Nullable(usize),
+    /// The trailing context of the rule with the given index matches
+    /// strings of different lengths
+    Trailing(usize),
+    /// The rules conflict with each other
+    Rules(sana_core::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pattern(i, err) =>
+                write!(f, "invalid pattern #{}: {}", i, err),
+            Error::Nullable(i) =>
+                write!(f, "the rule #{} matches the empty string", i),
+            Error::Trailing(i) =>
+                write!(f, "the trailing context of the rule #{} has a variable length", i),
+            Error::Rules(sana_core::Error::AmbiguityError(a, b, witness)) =>
+                write!(f, "both rule #{} and rule #{} match {:?}", a, b, witness),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A lexer specification constructed at runtime
+///
+/// It is the runtime counterpart of a `Sana` enum: `T` is the token type
+/// and `error` is the token produced on errors
+#[derive(Debug, Clone)]
+pub struct DynamicSana<T> {
+    /// The code of the lexer, its actions are the indices of the rules
+    code: Vec<Op<usize>>,
+    /// The token and the trailing context length of each rule
+    rules: Vec<(T, usize)>,
+    error: T,
+}
+
+impl<T: Clone + PartialEq> DynamicSana<T> {
+    /// Construct a lexer from the given rules
+    pub fn new(rules: &RuleSet<T>, error: T) -> Result<Self, Error> {
+        Self::with_trailing(rules, &[], error)
+    }
+
+    /// Construct a lexer from the given rules and their trailing contexts
+    ///
+    /// `trailing` has the trailing context of each rule, as in the `r1/r2`
+    /// rules of flex, see `Rule::followed_by`. The rules after the end of
+    /// the slice have no trailing context.
+    pub fn with_trailing(rules: &RuleSet<T>, trailing: &[Option<Regex>], error: T) -> Result<Self, Error> {
+        if let Some(i) = rules.rules.iter().position(|r| r.regex.is_nullable()) {
+            return Err(Error::Nullable(i))
+        }
+
+        let trailing = |i: usize| trailing.get(i).and_then(Option::as_ref);
+        let lengths = (0..rules.rules.len())
+            .map(|i| match trailing(i) {
+                Some(regex) => regex.fixed_length().ok_or(Error::Trailing(i)),
+                None => Ok(0),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let indexed = RuleSet {
+            rules: rules.rules.iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    let rule = Rule::new(r.regex.clone(), r.priority, i);
+
+                    match trailing(i) {
+                        Some(regex) => rule.followed_by(regex.clone()),
+                        None => rule,
+                    }
+                })
+                .collect()
+        };
+        let dfa = indexed.construct_dfa()
+            .map_err(Error::Rules)?
+            .minimize();
+        let mut ir = Ir::from_automata(dfa);
+        ir.optimize(Passes::ALL);
+        let code = ir.flatten();
+
+        let rules = rules.rules.iter()
+            .zip(lengths)
+            .map(|(r, len)| (r.action.clone(), len))
+            .collect();
+
+        Ok(DynamicSana { code, rules, error })
+    }
+
+    /// Construct a lexer from the given patterns and their tokens
+    ///
+    /// The patterns use the [regex](https://docs.rs/regex) crate syntax.
+    /// All rules have the same priority.
+    pub fn from_patterns(patterns: &[(&str, T)], error: T) -> Result<Self, Error> {
+        let rules = patterns.iter()
+            .enumerate()
+            .map(|(i, (pattern, token))| {
+                let hir = regex_syntax::Parser::new()
+                    .parse(pattern)
+                    .map_err(|e| Error::Pattern(i, e.to_string()))?;
+                let regex = Regex::try_from(hir)
+                    .map_err(|e| Error::Pattern(i, e.to_string()))?;
+
+                Ok(Rule { regex, priority: 0, action: token.clone() })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Self::new(&RuleSet { rules }, error)
+    }
+}
+
+impl<T: Clone> DynamicSana<T> {
+    /// Create a new `DynamicLexer` on the given input
+    pub fn lexer<'code, 'input>(&'code self, input: &'input str) -> DynamicLexer<'code, 'input, T> {
+        DynamicLexer {
+            vm: Vm::new(&self.code, input),
+            rules: &self.rules,
+            error: &self.error,
+        }
+    }
+}
+
+/// A lexer produced by `DynamicSana`
+///
+/// Like `Lexer`, it is an `Iterator` of tokens
+#[derive(Debug, Clone)]
+pub struct DynamicLexer<'code, 'input, T> {
+    vm: Vm<'code, 'input, usize>,
+    rules: &'code [(T, usize)],
+    error: &'code T,
+}
+
+impl<'code, 'input, T: Clone> DynamicLexer<'code, 'input, T> {
+    /// Set the cursor at position `pos`
+    pub fn rewind(&mut self, pos: usize) {
+        self.vm.cursor.rewind(pos)
+    }
+
+    /// The current position of the cursor
+    pub fn position(&self) -> usize {
+        self.vm.cursor.position()
+    }
+
+    /// The source string of the lexer
+    pub fn source(&self) -> &'input str {
+        self.vm.cursor.input
+    }
+}
+
+impl<'code, 'input, T: Clone> Iterator for DynamicLexer<'code, 'input, T> {
+    type Item = Spanned<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use sana_core::ir::VmResult::*;
+
+        let res = self.vm.run();
+        let rules = self.rules;
+        let token = match cut_trailing(res, &mut self.vm.cursor, |&i| rules[i].1) {
+            Action { start, end, action } =>
+                Spanned { start, end, value: rules[action].0.clone() },
+            Error { start, end } =>
+                Spanned { start, end, value: self.error.clone() },
+            Eoi => return None,
+        };
+
+        Some(token)
+    }
+}
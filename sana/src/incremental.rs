@@ -0,0 +1,266 @@
+//! Incremental re-lexing after edits
+//!
+//! A `TokenList` keeps the tokens of a source together with the information
+//! needed to update them after an edit. The lexer restarts after the last
+//! token that is not affected by the edit and stops as soon as it is in the
+//! same state as the old lexer was at some position after the edit. The rest
+//! of the old tokens are reused.
+//!
+//! A token is affected by an edit if the lexer read any character of the
+//! edited range while producing this or any previous token, so the lookahead
+//! of the lexer is taken into account.
+//!
+//! ```rust
+//! use sana::{Sana, Spanned};
+//! use sana::incremental::{TokenList, Relexed};
+//!
+//! #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+//! #[skip(" +")]
+//! enum Token {
+//!     #[regex("[a-z]+")]
+//!     Ident,
+//!     #[regex("[0-9]+")]
+//!     Number,
+//!     #[error]
+//!     Error,
+//! }
+//!
+//! let mut tokens = TokenList::<Token>::new("foo bar baz");
+//!
+//! // Replace "bar" with "42"
+//! let relexed = tokens.relex(4..7, "foo 42 baz");
+//!
+//! assert_eq!(relexed, Relexed { old: 1..2, new: 1..2 });
+//! assert_eq!(tokens.tokens()[1], Spanned { value: Token::Number, start: 4, end: 6 });
+//! assert_eq!(tokens.tokens()[2], Spanned { value: Token::Ident, start: 7, end: 10 });
+//! ```
+//!
+//! The lexer restarts with the default extras, so callbacks should not
+//! depend on the extras accumulated over the whole source.
+
+use std::ops::Range;
+
+use sana_core::ir::Input;
+
+use crate::{Lexer, Sana, Spanned};
+
+/// The tokens replaced by `TokenList::relex`
+///
+/// The tokens in the `old` range of the old list are replaced by the tokens
+/// in the `new` range of the updated list. The other tokens are the same,
+/// but the tokens after the edit are shifted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relexed {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// The tokens of a source, which can be updated after edits
+#[derive(Debug, Clone)]
+pub struct TokenList<Token> {
+    tokens: Vec<Spanned<Token>>,
+    /// For each token, the furthest position read by the lexer
+    /// until the token was produced
+    reads: Vec<usize>,
+    /// The mode stacks after the tokens, empty if the lexer has one mode
+    modes: Vec<Vec<usize>>,
+    /// The length of the source
+    len: usize,
+}
+
+/// A token together with the lexer state after it
+struct Lexed<Token> {
+    token: Spanned<Token>,
+    read: usize,
+    modes: Vec<usize>,
+}
+
+impl<'source, Token: Sana<'source> + PartialEq> TokenList<Token> {
+    /// Lex the whole source
+    pub fn new(source: &'source Token::Input) -> Self {
+        let mut list = TokenList {
+            tokens: vec![],
+            reads: vec![],
+            modes: vec![],
+            len: source.len(),
+        };
+
+        let mut lexer = Token::lexer(source);
+        while let Some(lexed) = Self::next(&mut lexer) {
+            list.push(lexed)
+        }
+
+        list
+    }
+
+    /// The tokens of the source
+    ///
+    /// Unlike `Lexer`, an error token that does not match anything covers
+    /// the next char, so the lexer always makes progress
+    pub fn tokens(&self) -> &[Spanned<Token>] {
+        &self.tokens
+    }
+
+    /// Update the tokens after the `edit` range of the old source is replaced
+    ///
+    /// `source` is the whole new source. Return the range of the replaced
+    /// tokens, which is empty if the tokens are only shifted. The tokens
+    /// overlapping the edit are always replaced, even if the new tokens
+    /// have the same spans.
+    pub fn relex(&mut self, edit: Range<usize>, source: &'source Token::Input) -> Relexed {
+        let delta = source.len() as isize - self.len as isize;
+        let edit_end = shift(edit.end, delta);
+        self.len = source.len();
+
+        // The reads only grow, so the unaffected tokens are a prefix
+        let keep = self.reads.partition_point(|&read| read < edit.start);
+
+        let mut lexer = Token::lexer(source);
+        if keep > 0 {
+            lexer.rewind(self.tokens[keep - 1].end);
+            if Token::MODES.len() > 1 {
+                lexer.modes = self.modes[keep - 1].clone();
+            }
+        }
+        let read_before = keep.checked_sub(1).map_or(0, |i| self.reads[i]);
+
+        let mut relexed = vec![];
+        // The index of the first old token after the resync point
+        let mut resync = self.tokens.len();
+        while let Some(mut lexed) = Self::next(&mut lexer) {
+            lexed.read = lexed.read.max(read_before);
+            let end = lexed.token.end;
+            let modes = lexed.modes.clone();
+            relexed.push(lexed);
+
+            if end < edit_end { continue }
+
+            // The old lexer was in the same state after this token
+            let old_end = shift(end, -delta);
+            let old = self.tokens[keep..]
+                .binary_search_by_key(&old_end, |tok| tok.end)
+                .map(|i| keep + i);
+
+            if let Ok(j) = old {
+                if Token::MODES.len() == 1 || self.modes[j] == modes {
+                    resync = j + 1;
+                    break
+                }
+            }
+        }
+
+        let changed = self.changed(keep, resync, &relexed, &edit, delta);
+        self.splice(keep, resync, relexed, delta);
+
+        changed
+    }
+
+    /// Find the replaced tokens, skipping the re-lexed tokens that
+    /// did not change
+    ///
+    /// The scans stop at the first token overlapping the edit, since its
+    /// text changed even if its span did not.
+    fn changed(
+        &self,
+        keep: usize,
+        resync: usize,
+        relexed: &[Lexed<Token>],
+        edit: &Range<usize>,
+        delta: isize,
+    ) -> Relexed {
+        let old = &self.tokens[keep..resync];
+
+        let prefix = old.iter()
+            .zip(relexed)
+            .take_while(|(old, new)| **old == new.token && !overlaps(old, edit))
+            .count();
+
+        let suffix = old[prefix..].iter().rev()
+            .zip(relexed[prefix..].iter().rev())
+            .take_while(|(old, new)| {
+                shift(old.start, delta) == new.token.start
+                    && shift(old.end, delta) == new.token.end
+                    && old.value == new.token.value
+                    && !overlaps(old, edit)
+            })
+            .count();
+
+        Relexed {
+            old: keep + prefix..resync - suffix,
+            new: keep + prefix..keep + relexed.len() - suffix,
+        }
+    }
+
+    /// Replace the old tokens between `keep` and `resync` with the re-lexed
+    /// ones and shift the rest of the tokens
+    fn splice(&mut self, keep: usize, resync: usize, relexed: Vec<Lexed<Token>>, delta: isize) {
+        let mut tokens = self.tokens.split_off(resync);
+        let mut reads = self.reads.split_off(resync);
+        let mut modes =
+            if Token::MODES.len() > 1 { self.modes.split_off(resync) }
+            else { vec![] };
+
+        self.tokens.truncate(keep);
+        self.reads.truncate(keep);
+        self.modes.truncate(keep);
+
+        for lexed in relexed {
+            self.push(lexed)
+        }
+
+        for tok in &mut tokens {
+            tok.start = shift(tok.start, delta);
+            tok.end = shift(tok.end, delta);
+        }
+
+        let mut read = self.reads.last().copied().unwrap_or(0);
+        for r in &mut reads {
+            read = read.max(shift(*r, delta));
+            *r = read;
+        }
+
+        self.tokens.append(&mut tokens);
+        self.reads.append(&mut reads);
+        self.modes.append(&mut modes);
+    }
+
+    fn push(&mut self, lexed: Lexed<Token>) {
+        self.tokens.push(lexed.token);
+        self.reads.push(lexed.read);
+        if Token::MODES.len() > 1 {
+            self.modes.push(lexed.modes)
+        }
+    }
+
+    /// Produce the next token, moving past the errors that match nothing
+    fn next(lexer: &mut Lexer<'source, Token>) -> Option<Lexed<Token>> {
+        let mut token = lexer.next()?;
+
+        if token.start == token.end {
+            let width = lexer.source()
+                .decode(token.start)
+                .map_or(0, |(_, width)| width);
+
+            token.end += width;
+            lexer.rewind(token.end);
+        }
+
+        let read = lexer.cursor.furthest();
+        let modes =
+            if Token::MODES.len() > 1 { lexer.modes.clone() }
+            else { vec![] };
+
+        Some(Lexed { token, read, modes })
+    }
+}
+
+fn shift(pos: usize, delta: isize) -> usize {
+    (pos as isize + delta) as usize
+}
+
+/// Whether the edit replaced any text of the token
+///
+/// An insertion overlaps the token if it is strictly inside of it
+fn overlaps<Token>(token: &Spanned<Token>, edit: &Range<usize>) -> bool {
+    token.start < edit.end && edit.start < token.end
+}
@@ -106,77 +106,422 @@
 //! walking the DFA, lexer remembers the last seen action state. When the teminal
 //! state is reached, the lexer returns the last action state or an error, if the
 //! lexer visited no action states.
+//!
+//! If the rules are only known at runtime, use `dynamic::DynamicSana` instead of
+//! the derive macro.
 
 pub use sana_derive::Sana;
 #[doc(hidden)]
 pub use sana_core::ir;
 
-use sana_core::ir::{Op, Vm};
+pub mod dynamic;
+pub mod incremental;
+pub mod lines;
+pub mod stream;
+pub mod restricted;
+pub mod table;
+
+use std::marker::PhantomData;
+
+use sana_core::ir::Cursor;
+use lines::{Columns, LineCol, LineIndex};
 
 /// Trait implemented for an enum representing all tokens.
 ///
 /// The trait implemented by `#[derive(Sana)]`. You should not implement it yourself.
-pub trait Sana: Sized + Clone + Copy {
+///
+/// The `'source` lifetime is the lifetime of the input. Variants filled by
+/// parse callbacks may borrow from it, for example `Ident(&'source str)`.
+pub trait Sana<'source>: Sized + Clone {
+    /// The input scanned by the lexer
+    ///
+    /// It is `str` by default, or `[u8]` for enums marked with `#[sana(bytes)]`
+    type Input: ?Sized + ir::Input + std::fmt::Debug + 'source;
+
+    /// The user state stored in the lexer
+    ///
+    /// It is set with `#[sana(extras = T)]` and is `()` by default
+    type Extras: Default;
+
+    /// The set of the variants, a `TokenSet` with enough words for all of
+    /// them
+    ///
+    /// `<Token as Sana>::Set` names the set type without a type alias.
+    /// `#[sana(set = Name)]` also generates an alias with the given name.
+    /// The number of words is not known to generic code, so it can only use
+    /// the set through the bounds below.
+    type Set: Default + Copy + Eq + std::fmt::Debug + std::iter::FromIterator<Self>;
+
     const ERROR: Self;
+
+    /// The names of the lexer modes
+    ///
+    /// The first mode is the initial one
+    const MODES: &'static [&'static str] = &["default"];
+
+    /// Match the next token and return the index of its variant
     #[doc(hidden)]
-    const USES_VM: bool = false;
+    fn lex(cursor: &mut Cursor<'_, Self::Input>) -> ir::VmResult<usize>;
 
     #[doc(hidden)]
-    fn ir() -> &'static [Op<Self>];
+    fn lex_mode(_mode: usize, cursor: &mut Cursor<'_, Self::Input>) -> ir::VmResult<usize> {
+        Self::lex(cursor)
+    }
+
+    /// Construct the token of the variant with the given index from the
+    /// matched slice
+    ///
+    /// Return `None` if the parse callback of the variant fails
+    #[doc(hidden)]
+    fn from_action(action: usize, slice: &'source Self::Input, extras: &mut Self::Extras) -> Option<Self>;
 
     #[doc(hidden)]
-    fn lex<'input>(_cursor: &mut ir::Cursor<'input>) -> ir::VmResult<Self> {
-        ir::VmResult::Eoi
+    fn mode_change(_action: usize) -> Option<ModeChange> {
+        None
     }
 
+    /// The index of the variant of the token
+    #[doc(hidden)]
+    fn index(&self) -> usize;
+
     /// Create a new `Lexer` that will produce tokens of this type
-    fn lexer(input: &str) -> Lexer<'_, Self> {
+    fn lexer(input: &'source Self::Input) -> Lexer<'source, Self> {
         Lexer::new(input)
     }
 }
 
+/// The result of a parse callback
+///
+/// Parse callbacks may return either `Option<T>` or `Result<T, E>`.
+/// `None` and `Err` produce the error token.
+#[doc(hidden)]
+pub trait IntoToken<T> {
+    fn into_token(self) -> Option<T>;
+}
+
+impl<T> IntoToken<T> for Option<T> {
+    fn into_token(self) -> Option<T> {
+        self
+    }
+}
+
+impl<T, E> IntoToken<T> for Result<T, E> {
+    fn into_token(self) -> Option<T> {
+        self.ok()
+    }
+}
+
+/// A change of the lexer mode after a token is matched
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModeChange {
+    Push(usize),
+    Pop,
+    Switch(usize),
+}
+
 /// The `Lexer` is an `Iterator` of tokens
-#[derive(Debug, Clone)]
-pub struct Lexer<'input, Token: Sana + 'static> {
-    vm: Vm<'static, 'input, Token>,
+#[derive(Debug)]
+pub struct Lexer<'source, Token: Sana<'source>> {
+    cursor: Cursor<'source, Token::Input>,
+    modes: Vec<usize>,
+    extras: Token::Extras,
+    lines: Option<LineIndex>,
 }
 
-impl<'input, Token: Sana> Lexer<'input, Token> {
+impl<'source, Token: Sana<'source>> Clone for Lexer<'source, Token>
+where Token::Extras: Clone {
+    fn clone(&self) -> Self {
+        Lexer {
+            cursor: self.cursor.clone(),
+            modes: self.modes.clone(),
+            extras: self.extras.clone(),
+            lines: self.lines.clone(),
+        }
+    }
+}
+
+impl<'source, Token: Sana<'source>> Lexer<'source, Token> {
     /// Create a new `Lexer` on the given input
     ///
     /// **NOTE:** for better type inference it's prefered
     /// to use `Sana::lexer` instead
-    pub fn new(input: &'input str) -> Self {
-        let ir = Token::ir();
-        let vm = Vm::new(ir, input);
+    pub fn new(input: &'source Token::Input) -> Self {
+        Self::with_extras(input, Default::default())
+    }
+
+    /// Create a new `Lexer` on the given input with the given extras
+    pub fn with_extras(input: &'source Token::Input, extras: Token::Extras) -> Self {
+        let cursor = Cursor::new(input);
 
-        Lexer { vm }
+        Lexer { cursor, modes: vec![0], extras, lines: None }
     }
 
     /// Morth the lexer into another lexer, which scans a different token
     ///
     /// The cursor position of the new lexer is the same as the cursor
-    /// position of the old lexer before the metamorphosis
-    pub fn morph<Lexeme: Sana + 'static>(self) -> Lexer<'input, Lexeme> {
-        let mut lexer = Lexeme::lexer(self.source());
-        lexer.rewind(self.position());
+    /// position of the old lexer before the metamorphosis. The new lexer
+    /// starts in its initial mode and takes the extras and the line index
+    /// of the old lexer
+    pub fn morph<Lexeme>(self) -> Lexer<'source, Lexeme>
+    where Lexeme: Sana<'source, Input = Token::Input, Extras = Token::Extras> {
+        let mut lexer = Lexer::with_extras(self.cursor.input, self.extras);
+        lexer.rewind(self.cursor.position());
+        lexer.lines = self.lines;
 
         lexer
     }
 
+    /// The user state of the lexer
+    ///
+    /// Its type is set with `#[sana(extras = T)]`. The `callback` functions
+    /// of the rules receive it as `&mut T`
+    pub fn extras(&self) -> &Token::Extras {
+        &self.extras
+    }
+
+    /// The mutable user state of the lexer
+    pub fn extras_mut(&mut self) -> &mut Token::Extras {
+        &mut self.extras
+    }
+
+    /// Destroy the lexer and return its user state
+    pub fn into_extras(self) -> Token::Extras {
+        self.extras
+    }
+
     /// Set the cursor at position `pos`
     pub fn rewind(&mut self, pos: usize) {
-        self.vm.cursor.rewind(pos)
+        self.cursor.rewind(pos)
     }
 
     /// The current position of the cursor
     pub fn position(&self) -> usize {
-        self.vm.cursor.position()
+        self.cursor.position()
     }
 
     /// The source string of the lexer
-    pub fn source(&self) -> &'input str {
-        self.vm.cursor.input
+    pub fn source(&self) -> &'source Token::Input {
+        self.cursor.input
+    }
+
+    /// Start tracking lines, counting the columns in the given way
+    ///
+    /// The lexer records the line starts as it produces tokens, so
+    /// `line_col` is cheap for the positions it has passed
+    pub fn track_lines(&mut self, columns: Columns) {
+        let mut lines = LineIndex::new(columns);
+        lines.advance(self.cursor.input, self.cursor.position());
+
+        self.lines = Some(lines)
+    }
+
+    /// The line and the column of the byte position `pos`
+    ///
+    /// Return `None` if the line tracking is not enabled with `track_lines`
+    pub fn line_col(&self, pos: usize) -> Option<LineCol> {
+        self.lines.as_ref()
+            .map(|lines| lines.line_col(self.cursor.input, pos))
+    }
+
+    /// The name of the current lexer mode
+    pub fn mode(&self) -> &'static str {
+        Token::MODES[self.current_mode()]
+    }
+
+    /// The depth of the mode stack
+    ///
+    /// It is equal to `1` in the initial mode
+    pub fn mode_depth(&self) -> usize {
+        self.modes.len()
+    }
+
+    fn current_mode(&self) -> usize {
+        *self.modes.last().unwrap()
+    }
+}
+
+impl ModeChange {
+    /// Apply the change to a mode stack
+    fn apply(self, modes: &mut Vec<usize>) {
+        match self {
+            ModeChange::Push(mode) => modes.push(mode),
+            // The initial mode is never popped
+            ModeChange::Pop if modes.len() > 1 => { modes.pop(); },
+            ModeChange::Pop => (),
+            ModeChange::Switch(mode) => *modes.last_mut().unwrap() = mode,
+        }
+    }
+}
+
+/// A set of token variants
+///
+/// It is a bitset of `N` words over the variant indices. The set type of
+/// an enum is `<Token as Sana>::Set`, and `#[sana(set = Name)]` generates
+/// a type alias with the given name.
+///
+/// The sets can be constructed in constant expressions using the variant
+/// indices, which are returned by the generated `index` method:
+///
+/// ```rust
+/// use sana::Sana;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+/// #[sana(set = Tokens)]
+/// enum Token {
+///     #[token("+")]
+///     Plus,
+///     #[token("-")]
+///     Minus,
+///     #[regex("[0-9]+")]
+///     Number,
+///     #[error]
+///     Error,
+/// }
+///
+/// const OPERATORS: Tokens = Tokens::new()
+///     .with(Token::Plus.index())
+///     .with(Token::Minus.index());
+///
+/// assert!(OPERATORS.contains(&Token::Minus));
+/// assert!(!OPERATORS.contains(&Token::Number));
+/// assert_eq!(OPERATORS.len(), 2);
+/// assert_eq!(Token::COUNT, 4);
+/// ```
+pub struct TokenSet<T, const N: usize> {
+    words: [u64; N],
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, const N: usize> TokenSet<T, N> {
+    /// Create an empty set
+    pub const fn new() -> Self {
+        TokenSet { words: [0; N], marker: PhantomData }
+    }
+
+    /// Add the variant with the given index to the set
+    pub const fn with(mut self, index: usize) -> Self {
+        self.words[index / 64] |= 1 << (index % 64);
+
+        self
+    }
+
+    /// Check if the set contains the variant with the given index
+    pub const fn contains_index(&self, index: usize) -> bool {
+        index / 64 < N && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// The variants in either of the sets
+    pub const fn union(mut self, other: Self) -> Self {
+        let mut i = 0;
+        while i < N {
+            self.words[i] |= other.words[i];
+            i += 1;
+        }
+
+        self
+    }
+
+    /// The variants in both sets
+    pub const fn intersection(mut self, other: Self) -> Self {
+        let mut i = 0;
+        while i < N {
+            self.words[i] &= other.words[i];
+            i += 1;
+        }
+
+        self
+    }
+
+    /// The variants in this set but not in the other one
+    pub const fn difference(mut self, other: Self) -> Self {
+        let mut i = 0;
+        while i < N {
+            self.words[i] &= !other.words[i];
+            i += 1;
+        }
+
+        self
+    }
+
+    /// The number of variants in the set
+    pub const fn len(&self) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < N {
+            len += self.words[i].count_ones() as usize;
+            i += 1;
+        }
+
+        len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The indices of the variants in the set, in the increasing order
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..N * 64).filter(move |&i| self.contains_index(i))
+    }
+
+    /// Add the variant of the token to the set
+    pub fn insert<'source>(&mut self, token: &T)
+    where T: Sana<'source> {
+        *self = self.with(token.index())
+    }
+
+    /// Remove the variant of the token from the set
+    pub fn remove<'source>(&mut self, token: &T)
+    where T: Sana<'source> {
+        let index = token.index();
+
+        self.words[index / 64] &= !(1 << (index % 64))
+    }
+
+    /// Check if the set contains the variant of the token
+    pub fn contains<'source>(&self, token: &T) -> bool
+    where T: Sana<'source> {
+        self.contains_index(token.index())
+    }
+}
+
+impl<T, const N: usize> Default for TokenSet<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Clone for TokenSet<T, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const N: usize> Copy for TokenSet<T, N> {}
+
+impl<T, const N: usize> PartialEq for TokenSet<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl<T, const N: usize> Eq for TokenSet<T, N> {}
+
+impl<T, const N: usize> std::fmt::Debug for TokenSet<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.indices()).finish()
+    }
+}
+
+impl<'source, T: Sana<'source>, const N: usize> std::iter::FromIterator<T> for TokenSet<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for token in iter {
+            set.insert(&token)
+        }
+
+        set
     }
 }
 
@@ -190,24 +535,60 @@ pub struct Spanned<T> {
     pub value: T,
 }
 
-impl<'input, Token: Sana> Iterator for Lexer<'input, Token> {
+impl<'source, Token: Sana<'source>> Iterator for Lexer<'source, Token> {
     type Item = Spanned<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        use sana_core::ir::VmResult::*;
+        let res = Token::lex_mode(self.current_mode(), &mut self.cursor);
+
+        self.token(res)
+    }
+}
+
+impl<'source, Token: restricted::Restricted<'source>> Lexer<'source, Token> {
+    /// Produce the next token, choosing only among the variants in the set
+    ///
+    /// The longest match of the allowed variants is produced, even if another
+    /// variant matches a longer string. `#[skip]` rules are always allowed.
+    /// If none of the variants match, the error token is produced, unless the
+    /// input ends before the mismatch. Then `None` is returned, as `next` does
+    /// for a partial token at the end of input.
+    ///
+    /// It is available for the enums with `#[sana(restricted)]`.
+    pub fn next_restricted<const N: usize>(&mut self, allowed: &TokenSet<Token, N>) -> Option<Spanned<Token>> {
+        let mode = self.current_mode();
+        let res = Token::lex_restricted(mode, &mut self.cursor, &|action| allowed.contains_index(action));
+
+        self.token(res)
+    }
+}
 
-        let res =
-            if Token::USES_VM { self.vm.run() }
-            else { Token::lex(&mut self.vm.cursor) };
+impl<'source, Token: Sana<'source>> Lexer<'source, Token> {
+    /// Turn the result of the lexer into a token
+    fn token(&mut self, res: ir::VmResult<usize>) -> Option<Spanned<Token>> {
+        use sana_core::ir::{Input, VmResult::*};
 
         let token = match res {
-            Action { start, end, action } =>
-                Spanned { start, end, value: action },
+            Action { start, end, action } => {
+                if let Some(change) = Token::mode_change(action) {
+                    change.apply(&mut self.modes)
+                }
+
+                let slice = self.cursor.input.slice(start, end);
+                let value = Token::from_action(action, slice, &mut self.extras)
+                    .unwrap_or(Token::ERROR);
+
+                Spanned { start, end, value }
+            },
             Error { start, end } =>
                 Spanned { start, end, value: Token::ERROR },
             Eoi => return None,
         };
 
+        if let Some(lines) = &mut self.lines {
+            lines.advance(self.cursor.input, token.end)
+        }
+
         Some(token)
     }
 }
@@ -0,0 +1,132 @@
+//! Line and column tracking
+//!
+//! Line tracking is opt-in. Once enabled with `Lexer::track_lines`, the lexer
+//! records the start of each line it passes, including the lines inside skipped
+//! input, and `Lexer::line_col` converts byte positions to lines and columns.
+//!
+//! ```rust
+//! use sana::Sana;
+//! use sana::lines::{Columns, LineCol};
+//!
+//! #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+//! #[skip("[ \t\n]+")]
+//! enum Token {
+//!     #[regex("[a-z]+")]
+//!     Ident,
+//!     #[error]
+//!     Error,
+//! }
+//!
+//! let mut lexer = Token::lexer("foo\n\tbar");
+//! lexer.track_lines(Columns::Tabs(4));
+//!
+//! let bar = lexer.nth(1).unwrap();
+//! assert_eq!(lexer.line_col(bar.start), Some(LineCol { line: 1, column: 4 }));
+//! ```
+
+use sana_core::ir::Input;
+
+/// How the columns are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columns {
+    /// Count bytes
+    Bytes,
+    /// Count chars, or bytes for `[u8]` inputs
+    Chars,
+    /// Count UTF-16 code units, as the Language Server Protocol does
+    Utf16,
+    /// Count chars, but move to the next multiple of the tab width on `'\t'`
+    Tabs(usize),
+}
+
+/// A line and a column, both starting from zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The start positions of the lines of the input seen so far
+#[derive(Debug, Clone)]
+pub(crate) struct LineIndex {
+    columns: Columns,
+    /// The byte positions of the line starts, the first one is `0`
+    starts: Vec<usize>,
+    /// The position up to which the input is scanned
+    scanned: usize,
+}
+
+impl LineIndex {
+    pub(crate) fn new(columns: Columns) -> Self {
+        LineIndex { columns, starts: vec![0], scanned: 0 }
+    }
+
+    /// Record the lines that start before `pos`
+    pub(crate) fn advance<I: Input + ?Sized>(&mut self, input: &I, pos: usize) {
+        if pos <= self.scanned { return }
+
+        let starts = &mut self.starts;
+        scan(input, self.scanned, pos, |start| starts.push(start));
+        self.scanned = pos;
+    }
+
+    /// Find the line and the column of the byte position `pos`
+    ///
+    /// The input after the scanned part is scanned without recording
+    /// the lines
+    pub(crate) fn line_col<I: Input + ?Sized>(&self, input: &I, pos: usize) -> LineCol {
+        let (mut line, mut start) = match self.starts.binary_search(&pos) {
+            Ok(line) => (line, pos),
+            Err(next) => (next - 1, self.starts[next - 1]),
+        };
+
+        if pos > self.scanned {
+            scan(input, self.scanned, pos, |next| {
+                line += 1;
+                start = next;
+            });
+        }
+
+        LineCol { line, column: self.column(input, start, pos) }
+    }
+
+    fn column<I: Input + ?Sized>(&self, input: &I, start: usize, pos: usize) -> usize {
+        if let Columns::Bytes = self.columns {
+            return pos - start
+        }
+
+        let mut column = 0;
+        let mut at = start;
+        while at < pos {
+            let (ch, width) = match input.decode(at) {
+                Some(decoded) => decoded,
+                None => break,
+            };
+
+            column = match self.columns {
+                Columns::Tabs(width) if ch == '\t' && width > 0 =>
+                    (column / width + 1) * width,
+                Columns::Utf16 => column + ch.len_utf16(),
+                _ => column + 1,
+            };
+            at += width;
+        }
+
+        column
+    }
+}
+
+/// Call `line` with the start of each line that begins in `from..=to`
+fn scan<I, F>(input: &I, from: usize, to: usize, mut line: F)
+where I: Input + ?Sized, F: FnMut(usize) {
+    let mut at = from;
+    while at < to {
+        let (ch, width) = match input.decode(at) {
+            Some(decoded) => decoded,
+            None => break,
+        };
+
+        at += width;
+        if ch == '\n' { line(at) }
+    }
+}
@@ -0,0 +1,103 @@
+//! Lexing restricted to a subset of tokens
+//!
+//! With `#[sana(restricted)]`, the derive generates a table-driven DFA for
+//! each mode, whose states hold all variants that match in them.
+//! `Lexer::next_restricted` walks this DFA and chooses the longest match
+//! among the allowed variants only.
+
+use sana_core::ir::{Cursor, Input, VmResult};
+
+use crate::Sana;
+
+/// A token type that can be lexed with `Lexer::next_restricted`
+///
+/// It is implemented by the derive for the enums with `#[sana(restricted)]`
+pub trait Restricted<'source>: Sana<'source> {
+    /// Match the next token among the variants for which `allowed` returns
+    /// `true`
+    #[doc(hidden)]
+    fn lex_restricted(
+        mode: usize,
+        cursor: &mut Cursor<'_, Self::Input>,
+        allowed: &dyn Fn(usize) -> bool,
+    ) -> VmResult<usize>;
+}
+
+/// A DFA with the states tagged by all variants that match in them
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Dfa {
+    /// The states of the DFA, the initial state is the first one
+    pub states: &'static [DfaState],
+}
+
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct DfaState {
+    /// The variants that match in this state, from the highest priority
+    /// to the lowest one
+    pub accept: &'static [usize],
+    /// The transitions `(start, end, state)` on inclusive char ranges,
+    /// sorted by the range
+    ///
+    /// Transitions to the states that never lead to a match are omitted
+    pub edges: &'static [(char, char, usize)],
+}
+
+impl DfaState {
+    fn transite(&self, ch: char) -> Option<usize> {
+        use std::cmp::Ordering::*;
+
+        self.edges
+            .binary_search_by(|&(start, end, _)| {
+                if end < ch { Less }
+                else if start > ch { Greater }
+                else { Equal }
+            })
+            .ok()
+            .map(|i| self.edges[i].2)
+    }
+}
+
+/// Find the longest match of the allowed variants
+#[doc(hidden)]
+pub fn run<I>(dfa: &Dfa, cursor: &mut Cursor<'_, I>, allowed: &dyn Fn(usize) -> bool) -> VmResult<usize>
+where I: Input + ?Sized {
+    if cursor.is_eoi() {
+        return VmResult::Eoi
+    }
+
+    let start = cursor.position();
+    let mut state = &dfa.states[0];
+    let mut last = None;
+
+    loop {
+        if let Some(&action) = state.accept.iter().find(|&&a| allowed(a)) {
+            last = Some((action, cursor.position()))
+        }
+
+        let next = cursor.head.and_then(|ch| state.transite(ch));
+        match next {
+            Some(next) => {
+                cursor.shift();
+                state = &dfa.states[next];
+            },
+            None => break,
+        }
+    }
+
+    match last {
+        Some((action, end)) => {
+            cursor.rewind(end);
+
+            VmResult::Action { start, end, action }
+        },
+        // A partial token at the end of input is not an error, as in the IR
+        None if cursor.is_eoi() => {
+            cursor.rewind(start);
+
+            VmResult::Eoi
+        },
+        None => VmResult::Error { start, end: cursor.position() },
+    }
+}
@@ -0,0 +1,281 @@
+//! Lexing of streamed input
+//!
+//! A `StreamLexer` pulls chunks of input from a `Read` instead of scanning
+//! a whole string, so the input does not have to fit in memory. Only the
+//! part of the input starting at the current token is kept in the buffer.
+//!
+//! If the lexer reaches the end of the buffer before the end of the stream,
+//! the token may continue in the next chunk. In this case, the lexer reads
+//! more input and scans the token again from its start.
+//!
+//! ```rust
+//! use sana::{Sana, Spanned};
+//! use sana::stream::StreamLexer;
+//!
+//! #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+//! #[skip(" +")]
+//! enum Token {
+//!     #[regex("[a-z]+")]
+//!     Ident,
+//!     #[error]
+//!     Error,
+//! }
+//!
+//! let input: &[u8] = b"foo barbaz";
+//! let mut lexer = StreamLexer::<_, Token>::with_chunk_size(input, 4);
+//!
+//! let tok = lexer.next().unwrap().unwrap();
+//! assert_eq!(tok, Spanned { value: Token::Ident, start: 0, end: 3 });
+//!
+//! let tok = lexer.next().unwrap().unwrap();
+//! assert_eq!(tok, Spanned { value: Token::Ident, start: 4, end: 10 });
+//! assert_eq!(lexer.slice(), "barbaz");
+//!
+//! assert!(lexer.next().is_none());
+//! ```
+
+use std::io::{self, Read};
+
+use sana_core::ir::{Cursor, Input};
+
+use crate::{Sana, Spanned};
+
+/// The default number of bytes read at once
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// An input that can be decoded from a buffer of bytes
+pub trait StreamInput: Input {
+    /// The length of the longest prefix of the buffer that forms a valid input
+    ///
+    /// If `eof` is set, the buffer is the end of the stream and must be
+    /// valid completely
+    fn valid_prefix(buf: &[u8], eof: bool) -> io::Result<usize>;
+
+    /// Convert a buffer validated by `valid_prefix` into the input
+    ///
+    /// # Safety
+    ///
+    /// The buffer must be a concatenation of prefixes accepted by
+    /// `valid_prefix`
+    unsafe fn from_valid(buf: &[u8]) -> &Self;
+}
+
+impl StreamInput for str {
+    fn valid_prefix(buf: &[u8], eof: bool) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(_) => Ok(buf.len()),
+            // An incomplete char at the end may be finished by the next chunk
+            Err(e) if e.error_len().is_none() && !eof => Ok(e.valid_up_to()),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    unsafe fn from_valid(buf: &[u8]) -> &Self {
+        // SAFETY: valid prefixes end at char boundaries, so their
+        // concatenation is valid UTF-8
+        unsafe { std::str::from_utf8_unchecked(buf) }
+    }
+}
+
+impl StreamInput for [u8] {
+    fn valid_prefix(buf: &[u8], _eof: bool) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    unsafe fn from_valid(buf: &[u8]) -> &Self {
+        buf
+    }
+}
+
+/// A token type that does not borrow the input
+///
+/// It is implemented for all `Sana` enums without a lifetime parameter,
+/// so their tokens can outlive the buffer of a `StreamLexer`
+pub trait StreamSana: for<'s> Sana<'s, Input = <Self as StreamSana>::Input, Extras = <Self as StreamSana>::Extras> {
+    type Input: ?Sized + StreamInput + std::fmt::Debug + 'static;
+    type Extras: Default;
+}
+
+impl<T, I, E> StreamSana for T
+where
+    T: for<'s> Sana<'s, Input = I, Extras = E>,
+    I: ?Sized + StreamInput + std::fmt::Debug + 'static,
+    E: Default,
+{
+    type Input = I;
+    type Extras = E;
+}
+
+/// A lexer that reads its input from a `Read`
+///
+/// It is an `Iterator` of tokens with absolute byte offsets. Reading errors
+/// and invalid UTF-8 in the input of a `str` lexer are yielded as `Err`.
+pub struct StreamLexer<R, Token: StreamSana> {
+    reader: R,
+    /// The input starting at the start of the last token
+    buf: Vec<u8>,
+    /// The absolute offset of the buffer start
+    offset: usize,
+    /// The start of the last token in the buffer
+    start: usize,
+    /// The position of the cursor in the buffer
+    pos: usize,
+    /// The length of the validated prefix of the buffer
+    ///
+    /// Each byte is validated once, when it is read
+    valid: usize,
+    /// Whether the reader is exhausted
+    eof: bool,
+    chunk_size: usize,
+    modes: Vec<usize>,
+    extras: <Token as StreamSana>::Extras,
+}
+
+impl<R: Read, Token: StreamSana> StreamLexer<R, Token> {
+    /// Create a new `StreamLexer` reading from the given reader
+    pub fn new(reader: R) -> Self {
+        Self::with_chunk_size(reader, CHUNK_SIZE)
+    }
+
+    /// Create a new `StreamLexer` that reads `chunk_size` bytes at once
+    ///
+    /// When a token is longer than the buffer, the buffer grows faster,
+    /// so long tokens are scanned only a few times
+    pub fn with_chunk_size(reader: R, chunk_size: usize) -> Self {
+        StreamLexer {
+            reader,
+            buf: vec![],
+            offset: 0,
+            start: 0,
+            pos: 0,
+            valid: 0,
+            eof: false,
+            chunk_size: chunk_size.max(1),
+            modes: vec![0],
+            extras: Default::default(),
+        }
+    }
+
+    /// The absolute position of the cursor
+    pub fn position(&self) -> usize {
+        self.offset + self.pos
+    }
+
+    /// The input of the last token
+    ///
+    /// It is empty before the first token
+    pub fn slice(&self) -> &<Token as StreamSana>::Input {
+        Self::input(&self.buf, self.valid).slice(self.start, self.pos)
+    }
+
+    /// The user state of the lexer
+    pub fn extras(&self) -> &<Token as StreamSana>::Extras {
+        &self.extras
+    }
+
+    /// The mutable user state of the lexer
+    pub fn extras_mut(&mut self) -> &mut <Token as StreamSana>::Extras {
+        &mut self.extras
+    }
+
+    /// The name of the current lexer mode
+    pub fn mode(&self) -> &'static str {
+        <Token as Sana<'_>>::MODES[*self.modes.last().unwrap()]
+    }
+
+    /// The validated part of the buffer
+    fn input(buf: &[u8], valid: usize) -> &<Token as StreamSana>::Input {
+        // SAFETY: the buffer is validated up to `valid` in `fill`, and
+        // dropping a prefix up to the cursor keeps it valid
+        unsafe { <Token as StreamSana>::Input::from_valid(&buf[..valid]) }
+    }
+
+    /// Drop the input before the cursor, read the next chunk and validate it
+    fn fill(&mut self) -> io::Result<()> {
+        self.buf.drain(..self.pos);
+        self.offset += self.pos;
+        self.valid -= self.pos;
+        self.start = 0;
+        self.pos = 0;
+
+        let len = self.buf.len();
+        self.buf.resize(len + self.chunk_size.max(len), 0);
+
+        let read = loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        };
+
+        match read {
+            Ok(read) => {
+                self.buf.truncate(len + read);
+                self.eof = read == 0;
+
+                let tail = &self.buf[self.valid..];
+                self.valid += <Token as StreamSana>::Input::valid_prefix(tail, self.eof)?;
+
+                Ok(())
+            },
+            Err(e) => {
+                self.buf.truncate(len);
+
+                Err(e)
+            },
+        }
+    }
+
+    fn lex(&mut self) -> io::Result<Option<Spanned<Token>>> {
+        use sana_core::ir::VmResult::*;
+
+        loop {
+            let input = Self::input(&self.buf, self.valid);
+            let mut cursor = Cursor::new(input);
+            cursor.rewind(self.pos);
+
+            let mode = *self.modes.last().unwrap();
+            let res = Token::lex_mode(mode, &mut cursor);
+
+            if cursor.eoi_seen() && !self.eof {
+                self.fill()?;
+                continue
+            }
+
+            self.pos = cursor.position();
+
+            let (start, end, value) = match res {
+                Action { start, end, action } => {
+                    if let Some(change) = Token::mode_change(action) {
+                        change.apply(&mut self.modes)
+                    }
+
+                    let value = Token::from_action(action, input.slice(start, end), &mut self.extras)
+                        .unwrap_or(Token::ERROR);
+
+                    (start, end, value)
+                },
+                Error { start, end } => (start, end, Token::ERROR),
+                Eoi => return Ok(None),
+            };
+
+            self.start = start;
+
+            let token = Spanned {
+                start: self.offset + start,
+                end: self.offset + end,
+                value,
+            };
+
+            return Ok(Some(token))
+        }
+    }
+}
+
+impl<R: Read, Token: StreamSana> Iterator for StreamLexer<R, Token> {
+    type Item = io::Result<Spanned<Token>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lex().transpose()
+    }
+}
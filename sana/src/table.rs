@@ -0,0 +1,90 @@
+//! The runtime of the table-driven backend
+//!
+//! A lexer derived with `#[backend(table)]` has a static transition table
+//! for each mode instead of the generated code. The chars are mapped to
+//! equivalence classes, which are treated the same by every state, and the
+//! next state is looked up in a `state × class` matrix.
+
+use sana_core::ir::{Cursor, Input, VmResult};
+
+/// The next state for the transitions into the dead state
+#[doc(hidden)]
+pub const DEAD: u32 = u32::MAX;
+
+/// A transition table of a DFA
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Table {
+    /// The classes of the ASCII chars
+    pub ascii: [u32; 128],
+    /// The classes of the non-ASCII char ranges `(start, end, class)`,
+    /// sorted by the range
+    pub ranges: &'static [(char, char, u32)],
+    /// The number of the classes
+    pub class_count: usize,
+    /// The next state for each state and class, row by row
+    pub transitions: &'static [u32],
+    /// The action of each state, the initial state is the first one
+    pub actions: &'static [Option<usize>],
+}
+
+impl Table {
+    fn class(&self, ch: char) -> u32 {
+        if ch.is_ascii() {
+            return self.ascii[ch as usize]
+        }
+
+        let i = self.ranges.partition_point(|&(_, end, _)| end < ch);
+        self.ranges[i].2
+    }
+
+    fn transite(&self, state: usize, ch: char) -> Option<usize> {
+        let class = self.class(ch) as usize;
+        let next = self.transitions[state * self.class_count + class];
+
+        if next == DEAD { None }
+        else { Some(next as usize) }
+    }
+}
+
+/// Find the longest match, in the same way as the IR does
+#[doc(hidden)]
+pub fn run<I>(table: &Table, cursor: &mut Cursor<'_, I>) -> VmResult<usize>
+where I: Input + ?Sized {
+    if cursor.is_eoi() {
+        return VmResult::Eoi
+    }
+
+    let start = cursor.position();
+    let mut state = 0;
+    let mut last = None;
+
+    loop {
+        if let Some(action) = table.actions[state] {
+            last = Some((action, cursor.position()))
+        }
+
+        let next = cursor.head.and_then(|ch| table.transite(state, ch));
+        match next {
+            Some(next) => {
+                cursor.shift();
+                state = next;
+            },
+            None => break,
+        }
+    }
+
+    match last {
+        Some((action, end)) => {
+            if end != cursor.position() { cursor.rewind(end) }
+
+            VmResult::Action { start, end, action }
+        },
+        None if cursor.is_eoi() => {
+            cursor.rewind(start);
+
+            VmResult::Eoi
+        },
+        None => VmResult::Error { start, end: cursor.position() },
+    }
+}
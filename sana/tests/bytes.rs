@@ -0,0 +1,74 @@
+use sana::{Sana, Spanned};
+
+#[test]
+fn bytes() {
+    #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+    #[sana(bytes)]
+    enum Token {
+        #[regex("[a-z]+")]
+        Word,
+        #[regex(r"(?-u)[\x80-\xFF]+")]
+        High,
+        #[token(b"\x00\x01")]
+        Magic,
+        #[token(" ")]
+        Space,
+
+        #[error]
+        Error,
+    }
+
+    let input: &[u8] = b"abc \xE9\xFF\x00\x01 x";
+    let mut lexer = Token::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Word, start: 0, end: 3 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Space, start: 3, end: 4 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::High, start: 4, end: 6 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Magic, start: 6, end: 8 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Space, start: 8, end: 9 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Word, start: 9, end: 10 });
+
+    assert!(lexer.next().is_none());
+}
+
+#[test]
+fn bytes_vm() {
+    #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+    #[sana(bytes)]
+    #[backend(vm)]
+    enum Token {
+        #[regex(r"(?-u:\xFF)+")]
+        Ff,
+        #[regex("[α-ω]+")]
+        Greek,
+
+        #[error]
+        Error,
+    }
+
+    let input = "αβγ".as_bytes().iter()
+        .chain(b"\xFF\xFF\xFE")
+        .copied()
+        .collect::<Vec<u8>>();
+    let mut lexer = Token::lexer(&input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Greek, start: 0, end: 6 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Ff, start: 6, end: 8 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Error, start: 8, end: 8 });
+}
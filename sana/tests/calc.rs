@@ -35,7 +35,7 @@ fn calc_sin() {
     }
 
     let input = "sin(3.14)";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Ident, start: 0, end: 3 });
@@ -63,7 +63,7 @@ fn dot_vm() {
         Error,
     }
     let input = ".";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Dot, start: 0, end: 1 });
@@ -82,7 +82,7 @@ fn dot_ct() {
         Error,
     }
     let input = ".";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Dot, start: 0, end: 1 });
@@ -0,0 +1,80 @@
+use sana::{Sana, Spanned};
+
+fn ident(s: &str) -> Option<&str> {
+    Some(s)
+}
+
+fn hex(s: &str) -> Result<u32, std::num::ParseIntError> {
+    u32::from_str_radix(&s[2..], 16)
+}
+
+fn short(s: &str) -> Option<()> {
+    if s.len() <= 3 { Some(()) } else { None }
+}
+
+#[derive(Debug, Clone, PartialEq, Sana)]
+enum Token<'s> {
+    #[regex("[a-z]+", parse = ident)]
+    Ident(&'s str),
+    #[regex("[0-9]+", parse = str::parse)]
+    Integer(u8),
+    #[regex("0x[0-9a-fA-F]+", parse = hex, priority = 1)]
+    Hex(u32),
+    #[regex("[#]+", parse = short)]
+    Hashes,
+    #[regex("[ ]+")]
+    Whitespace,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Sana)]
+#[sana(bytes)]
+#[backend(vm)]
+enum ByteToken<'s> {
+    #[regex("[a-z]+", parse = Some)]
+    Word(&'s [u8]),
+    #[token(" ")]
+    Space,
+
+    #[error]
+    Error,
+}
+
+#[test]
+fn parse_callbacks() {
+    let mut lexer = Token::lexer("abc 42 0xff");
+
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Ident("abc"), start: 0, end: 3 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Whitespace, start: 3, end: 4 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Integer(42), start: 4, end: 6 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Whitespace, start: 6, end: 7 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Hex(255), start: 7, end: 11 }));
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn failed_callbacks() {
+    let mut lexer = Token::lexer("256 ### ####x");
+
+    // The whole match becomes the error token
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Error, start: 0, end: 3 }));
+    lexer.next();
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Hashes, start: 4, end: 7 }));
+    lexer.next();
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Error, start: 8, end: 12 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Ident("x"), start: 12, end: 13 }));
+}
+
+#[test]
+fn borrowed_bytes() {
+    let tokens: Vec<_> = ByteToken::lexer(b"ab cd")
+        .map(|tok| tok.value)
+        .collect();
+
+    assert_eq!(
+        tokens,
+        vec![ByteToken::Word(b"ab"), ByteToken::Space, ByteToken::Word(b"cd")]
+    );
+}
@@ -0,0 +1,92 @@
+use sana::{Sana, Spanned};
+use sana::dynamic::{DynamicSana, Error, Regex, Rule, RuleSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Ident,
+    Let,
+    Number,
+    Whitespace,
+    Error,
+}
+
+#[test]
+fn from_rules() {
+    let rule = |regex, priority, action| Rule { regex, priority, action };
+    let rules = RuleSet {
+        rules: vec![
+            rule(Regex::literal_str("let"), 1, Token::Let),
+            rule(Regex::Repeat { regex: Box::new(Regex::literal_str("x")), min: 1, max: None }, 0, Token::Ident),
+        ]
+    };
+    let sana = DynamicSana::new(&rules, Token::Error).unwrap();
+
+    let tokens: Vec<_> = sana.lexer("letxx").collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Spanned { value: Token::Let, start: 0, end: 3 },
+            Spanned { value: Token::Ident, start: 3, end: 5 },
+        ]
+    );
+}
+
+#[test]
+fn same_as_derive() {
+    #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+    enum Derived {
+        #[regex("[a-z]+")]
+        Ident,
+        #[regex("[0-9]+")]
+        Number,
+        #[regex("[ \t]+")]
+        Whitespace,
+        #[error]
+        Error,
+    }
+
+    let sana = DynamicSana::from_patterns(&[
+        ("[a-z]+", Token::Ident),
+        ("[0-9]+", Token::Number),
+        ("[ \t]+", Token::Whitespace),
+    ], Token::Error).unwrap();
+
+    let input = "abc 12\t!x 9";
+    let to_token = |tok: Derived| match tok {
+        Derived::Ident => Token::Ident,
+        Derived::Number => Token::Number,
+        Derived::Whitespace => Token::Whitespace,
+        Derived::Error => Token::Error,
+    };
+
+    let gold: Vec<_> = Derived::lexer(input)
+        .map(|Spanned { value, start, end }| Spanned { value: to_token(value), start, end })
+        .take(32)
+        .collect();
+    let tokens: Vec<_> = sana.lexer(input).take(32).collect();
+
+    assert_eq!(tokens, gold);
+}
+
+#[test]
+fn rewind() {
+    let sana = DynamicSana::from_patterns(&[("[0-9]+", Token::Number)], Token::Error).unwrap();
+    let mut lexer = sana.lexer("12 34");
+
+    lexer.rewind(3);
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Number, start: 3, end: 5 }));
+    assert_eq!(lexer.position(), 5);
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn errors() {
+    let err = DynamicSana::from_patterns(&[("[a-z", Token::Ident)], Token::Error);
+    assert!(matches!(err, Err(Error::Pattern(0, _))));
+
+    let err = DynamicSana::from_patterns(&[("x", Token::Ident), ("y*", Token::Let)], Token::Error);
+    assert_eq!(err.unwrap_err(), Error::Nullable(1));
+
+    let err = DynamicSana::from_patterns(&[("[a-z]+", Token::Ident), ("let", Token::Let)], Token::Error);
+    assert_eq!(err.unwrap_err().to_string(), "both rule #0 and rule #1 match \"let\"");
+}
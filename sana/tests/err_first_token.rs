@@ -14,7 +14,7 @@ fn err_first_token() {
     }
 
     let input = "x y";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Xy, start: 0, end: 1 });
@@ -8,7 +8,7 @@ fn everything_is_an_error() {
         Error,
     }
     let input = "asd";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Error, start: 0, end: 0 });
@@ -24,7 +24,7 @@ fn error_after_match() {
         Error,
     }
     let input = "xyz";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::X, start: 0, end: 1 });
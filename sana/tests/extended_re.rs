@@ -14,7 +14,7 @@ fn or() {
     }
 
     let input = "; 1,23 .";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::PunctOrDigit, start: 0, end: 1 }); // ;
@@ -55,7 +55,7 @@ fn or_attribute() {
     }
 
     let input = "A B";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::AorB, start: 0, end: 1 }); // A
@@ -73,7 +73,7 @@ fn or_attribute() {
 fn and_nonintersect_rules() {
     #[derive(Clone, Copy, Sana, PartialEq, Debug)]
     enum Token {
-        #[regex("[[:punct:]]+" & "\\d+")] // does not make any sense
+        #[regex("[[:punct:]]+" & "\\d+", allow_unreachable)] // does not make any sense
         #[allow(dead_code)]
         PunctAndDigit,
 
@@ -82,7 +82,7 @@ fn and_nonintersect_rules() {
     }
 
     let input = ";";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Error, start: 0, end: 0 });
@@ -103,7 +103,7 @@ fn and_intersect_rules() {
     }
 
     let input = "32 1";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Digits, start: 0, end: 2 });
@@ -131,7 +131,7 @@ fn and_not() {
     }
 
     let input = "/**/";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::CommentStart, start: 0, end: 2 });
@@ -156,7 +156,7 @@ fn concat() {
     }
 
     let input = ",23 ";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::PunctThenDigit, start: 0, end: 3 });
@@ -0,0 +1,131 @@
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct State {
+    lines: usize,
+    depth: usize,
+    idents: Vec<String>,
+}
+
+fn newline(_: &str, state: &mut State) -> Option<()> {
+    state.lines += 1;
+
+    Some(())
+}
+
+fn open(_: &str, state: &mut State) -> Option<usize> {
+    state.depth += 1;
+
+    Some(state.depth)
+}
+
+fn close(_: &str, state: &mut State) -> Option<()> {
+    state.depth = state.depth.checked_sub(1)?;
+
+    Some(())
+}
+
+fn intern(s: &str, state: &mut State) -> Option<usize> {
+    let ix = match state.idents.iter().position(|id| id == s) {
+        Some(ix) => ix,
+        None => {
+            state.idents.push(s.to_string());
+            state.idents.len() - 1
+        },
+    };
+
+    Some(ix)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(extras = State)]
+enum Token {
+    #[regex("[a-z]+", callback = intern)]
+    Ident(usize),
+    #[regex("[0-9]+", parse = str::parse)]
+    Integer(u32),
+    #[token("(", callback = open)]
+    Open(usize),
+    #[token(")", callback = close)]
+    Close,
+    #[token("\n", callback = newline)]
+    Newline,
+    #[token(" ")]
+    Space,
+    #[token("!")]
+    Bang,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(extras = State)]
+enum Other {
+    #[token("!", callback = newline)]
+    Bang,
+    #[error]
+    Error,
+}
+
+#[test]
+fn extras_in_callbacks() {
+    let mut lexer = Token::lexer("a (b 1\n(a))");
+    let tokens: Vec<_> = lexer.by_ref()
+        .filter(|tok| tok.value != Token::Space)
+        .map(|tok| tok.value)
+        .collect();
+
+    assert_eq!(tokens, vec![
+        Token::Ident(0),
+        Token::Open(1),
+        Token::Ident(1),
+        Token::Integer(1),
+        Token::Newline,
+        Token::Open(2),
+        Token::Ident(0),
+        Token::Close,
+        Token::Close,
+    ]);
+
+    let state = lexer.into_extras();
+    assert_eq!(state.lines, 1);
+    assert_eq!(state.depth, 0);
+    assert_eq!(state.idents, vec!["a", "b"]);
+}
+
+#[test]
+fn failed_extras_callback() {
+    let mut lexer = Token::lexer(")");
+
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Error, start: 0, end: 1 }));
+    assert_eq!(lexer.extras().depth, 0);
+}
+
+#[test]
+fn with_extras() {
+    let state = State { lines: 10, ..State::default() };
+    let mut lexer = sana::Lexer::<Token>::with_extras("\n", state);
+
+    assert_eq!(lexer.next().map(|tok| tok.value), Some(Token::Newline));
+    assert_eq!(lexer.extras().lines, 11);
+
+    lexer.extras_mut().lines = 0;
+    assert_eq!(lexer.extras().lines, 0);
+}
+
+#[test]
+fn morph_keeps_extras() {
+    let mut lexer = Token::lexer("\n!!\n");
+
+    assert_eq!(lexer.next().map(|tok| tok.value), Some(Token::Newline));
+
+    let mut other: sana::Lexer<Other> = lexer.morph();
+    assert_eq!(other.next().map(|tok| tok.value), Some(Other::Bang));
+    assert_eq!(other.extras().lines, 2);
+
+    let mut lexer: sana::Lexer<Token> = other.morph();
+    assert_eq!(lexer.next().map(|tok| tok.value), Some(Token::Bang));
+    assert_eq!(lexer.next().map(|tok| tok.value), Some(Token::Newline));
+    assert_eq!(lexer.extras().lines, 3);
+}
@@ -0,0 +1,173 @@
+use sana::{Sana, Spanned};
+use sana::incremental::{Relexed, TokenList};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip("[ \n]+")]
+enum Token {
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("[0-9]+")]
+    Number,
+    #[regex(r"[0-9]+\.[0-9]+")]
+    Float,
+    #[token(".")]
+    Dot,
+    #[token("abcdef", priority = 1)]
+    Long,
+    #[token("/*", push = comment)]
+    CommentStart,
+
+    #[token("*/", pop)]
+    #[mode(comment)]
+    CommentEnd,
+    #[regex(r"[^*]+|\*")]
+    #[mode(comment)]
+    CommentText,
+
+    #[error]
+    Error,
+}
+
+fn replace(source: &str, edit: std::ops::Range<usize>, text: &str) -> String {
+    format!("{}{}{}", &source[..edit.start], text, &source[edit.end..])
+}
+
+#[test]
+fn replace_token() {
+    let old = "foo bar 12 baz";
+    let mut list = TokenList::<Token>::new(old);
+
+    let new = replace(old, 4..7, "1");
+    let relexed = list.relex(4..7, &new);
+
+    assert_eq!(relexed, Relexed { old: 1..2, new: 1..2 });
+    assert_eq!(list.tokens(), TokenList::<Token>::new(&new).tokens());
+}
+
+#[test]
+fn only_shifted() {
+    let old = "foo bar";
+    let mut list = TokenList::<Token>::new(old);
+
+    let new = replace(old, 3..3, "   ");
+    let relexed = list.relex(3..3, &new);
+
+    assert_eq!(relexed, Relexed { old: 1..1, new: 1..1 });
+    assert_eq!(list.tokens()[1], Spanned { value: Token::Ident, start: 7, end: 10 });
+}
+
+#[test]
+fn same_span() {
+    // The edited token has the same kind and span
+    let old = "foo bar";
+    let mut list = TokenList::<Token>::new(old);
+
+    let new = replace(old, 6..7, "z");
+    let relexed = list.relex(6..7, &new);
+
+    assert_eq!(relexed, Relexed { old: 1..2, new: 1..2 });
+}
+
+#[test]
+fn lookahead() {
+    // The lexer reads "12.x" before it falls back to "12"
+    let old = "a 12.x";
+    let mut list = TokenList::<Token>::new(old);
+    assert_eq!(list.tokens()[1], Spanned { value: Token::Number, start: 2, end: 4 });
+
+    let new = replace(old, 5..6, "5");
+    let relexed = list.relex(5..6, &new);
+
+    assert_eq!(relexed, Relexed { old: 1..4, new: 1..2 });
+    assert_eq!(list.tokens()[1], Spanned { value: Token::Float, start: 2, end: 6 });
+    assert_eq!(list.tokens(), TokenList::<Token>::new(&new).tokens());
+}
+
+#[test]
+fn modes() {
+    let old = "a /* b */ c";
+    let mut list = TokenList::<Token>::new(old);
+
+    // Remove the end of the comment
+    let new = replace(old, 7..9, "");
+    list.relex(7..9, &new);
+
+    assert_eq!(list.tokens(), TokenList::<Token>::new(&new).tokens());
+    assert_eq!(list.tokens().last().unwrap().value, Token::CommentText);
+}
+
+#[test]
+fn errors_make_progress() {
+    let list = TokenList::<Token>::new("a ?? b");
+
+    assert_eq!(list.tokens(), &[
+        Spanned { value: Token::Ident, start: 0, end: 1 },
+        Spanned { value: Token::Error, start: 2, end: 3 },
+        Spanned { value: Token::Error, start: 3, end: 4 },
+        Spanned { value: Token::Ident, start: 5, end: 6 },
+    ]);
+}
+
+#[test]
+fn random_edits() {
+    const PIECES: &[&str] = &["", " ", "a", "b1", "abc", "def", "/*", "*/", "*", "9", ".", "\n", "?"];
+
+    // A simple linear congruential generator
+    let mut seed = 42u64;
+    let mut random = |n: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % n
+    };
+
+    let mut sources = vec!["abcdef x /* y */ 12 z".to_string()];
+    for _ in 0..500 {
+        let old = sources.last().unwrap();
+        let start = random(old.len() + 1);
+        let end = start + random(old.len() - start + 1).min(4);
+        let text = PIECES[random(PIECES.len())];
+
+        let new = replace(old, start..end, text);
+        sources.push(new);
+    }
+
+    let mut list = TokenList::<Token>::new(&sources[0]);
+    for pair in sources.windows(2) {
+        let (old, new) = (&pair[0], &pair[1]);
+        let start = old.bytes().zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let common_end = old[start..].bytes().rev()
+            .zip(new[start..].bytes().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let edit = start..old.len() - common_end;
+        let new_edit = start..new.len() - common_end;
+        let before = list.tokens().to_vec();
+        let relexed = list.relex(edit.clone(), new);
+        let expected = TokenList::<Token>::new(new);
+
+        // The tokens overlapping the edit are always replaced
+        for (i, tok) in before.iter().enumerate() {
+            if overlaps(tok, &edit) {
+                assert!(relexed.old.contains(&i), "{:?} -> {:?}: {:?}", old, new, tok);
+            }
+        }
+        for (i, tok) in expected.tokens().iter().enumerate() {
+            if overlaps(tok, &new_edit) {
+                assert!(relexed.new.contains(&i), "{:?} -> {:?}: {:?}", old, new, tok);
+            }
+        }
+
+        assert_eq!(list.tokens(), expected.tokens(), "{:?} -> {:?}", old, new);
+        assert_eq!(
+            before[relexed.old.end..].len(),
+            list.tokens()[relexed.new.end..].len(),
+        );
+        assert_eq!(&before[..relexed.old.start], &list.tokens()[..relexed.new.start]);
+    }
+}
+
+fn overlaps(tok: &Spanned<Token>, edit: &std::ops::Range<usize>) -> bool {
+    tok.start < edit.end && edit.start < tok.end
+}
@@ -0,0 +1,96 @@
+use sana::Sana;
+use sana::lines::{Columns, LineCol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip("[ \t\r\n]+")]
+enum Token {
+    #[regex("[^ \t\r\n]+")]
+    Word,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(bytes)]
+enum ByteToken {
+    #[regex("[a-z\n]+")]
+    Text,
+
+    #[error]
+    Error,
+}
+
+fn positions(input: &str, columns: Columns) -> Vec<(usize, usize)> {
+    let mut lexer = Token::lexer(input);
+    lexer.track_lines(columns);
+
+    let starts: Vec<_> = lexer.by_ref()
+        .map(|tok| tok.start)
+        .collect();
+
+    starts.into_iter()
+        .map(|pos| lexer.line_col(pos).unwrap())
+        .map(|LineCol { line, column }| (line, column))
+        .collect()
+}
+
+#[test]
+fn lines() {
+    assert_eq!(
+        positions("a bc\n\nd\r\n  e", Columns::Chars),
+        vec![(0, 0), (0, 2), (2, 0), (3, 2)]
+    );
+}
+
+#[test]
+fn columns() {
+    let input = "é\tx\n💖 y";
+
+    assert_eq!(positions(input, Columns::Bytes), vec![(0, 0), (0, 3), (1, 0), (1, 5)]);
+    assert_eq!(positions(input, Columns::Chars), vec![(0, 0), (0, 2), (1, 0), (1, 2)]);
+    assert_eq!(positions(input, Columns::Utf16), vec![(0, 0), (0, 2), (1, 0), (1, 3)]);
+    assert_eq!(positions(input, Columns::Tabs(4)), vec![(0, 0), (0, 4), (1, 0), (1, 2)]);
+}
+
+#[test]
+fn disabled_by_default() {
+    let mut lexer = Token::lexer("a\nb");
+    lexer.next();
+
+    assert_eq!(lexer.line_col(0), None);
+}
+
+#[test]
+fn ahead_of_lexer() {
+    let mut lexer = Token::lexer("a\nb\n c");
+    lexer.track_lines(Columns::Chars);
+
+    assert_eq!(lexer.line_col(5), Some(LineCol { line: 2, column: 1 }));
+    assert_eq!(lexer.line_col(6), Some(LineCol { line: 2, column: 2 }));
+
+    lexer.next();
+    assert_eq!(lexer.line_col(5), Some(LineCol { line: 2, column: 1 }));
+}
+
+#[test]
+fn after_rewind_and_morph() {
+    let mut lexer = Token::lexer("a\nb\nc");
+    lexer.track_lines(Columns::Chars);
+
+    assert_eq!(lexer.by_ref().count(), 3);
+    lexer.rewind(0);
+    assert_eq!(lexer.line_col(4), Some(LineCol { line: 2, column: 0 }));
+
+    let lexer: sana::Lexer<Token> = lexer.morph();
+    assert_eq!(lexer.line_col(2), Some(LineCol { line: 1, column: 0 }));
+}
+
+#[test]
+fn bytes() {
+    let mut lexer = ByteToken::lexer(b"ab\ncd");
+    lexer.track_lines(Columns::Chars);
+
+    assert_eq!(lexer.next().map(|tok| tok.end), Some(5));
+    assert_eq!(lexer.line_col(4), Some(LineCol { line: 1, column: 1 }));
+}
@@ -0,0 +1,35 @@
+//! Tokens much longer than the stack
+
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+enum Token {
+    #[regex("(a[bc]|d)+")]
+    Chain,
+    #[regex("\"([^\"\\\\]|\\\\.)*\"")]
+    String,
+
+    #[error]
+    Error,
+}
+
+/// Lex the input on a thread with a small stack
+fn lex(input: String) -> Vec<Spanned<Token>> {
+    std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(move || Token::lexer(input.as_str()).collect())
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn constant_stack() {
+    let input = "abacd".repeat(200_000);
+    let len = input.len();
+    assert_eq!(lex(input), vec![Spanned { value: Token::Chain, start: 0, end: len }]);
+
+    let input = format!("\"{}\"", "x\\\"".repeat(200_000));
+    let len = input.len();
+    assert_eq!(lex(input), vec![Spanned { value: Token::String, start: 0, end: len }]);
+}
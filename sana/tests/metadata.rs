@@ -0,0 +1,82 @@
+use sana::Sana;
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+enum Token {
+    #[token("(")]
+    LParen,
+    #[regex("[a-z]+", allow_unreachable)]
+    #[allow(dead_code)]
+    Ident,
+    #[regex("[0-9]+", parse = parse_number)]
+    Number(u32),
+    #[regex("[a-z]+" & !"let", priority = 1)]
+    Name,
+    #[token("let", priority = 2)]
+    Let,
+    #[token("==")]
+    #[token("=")]
+    Eq,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(bytes)]
+enum ByteToken {
+    #[token(b"\xff\xfe")]
+    Bom,
+    #[token(b"ab")]
+    Ab,
+
+    #[error]
+    Error,
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    s.parse().ok()
+}
+
+#[test]
+fn names() {
+    assert_eq!(Token::LParen.name(), "LParen");
+    assert_eq!(Token::Number(1).name(), "Number");
+    assert_eq!(Token::Error.name(), "Error");
+}
+
+#[test]
+fn patterns() {
+    assert_eq!(Token::LParen.pattern(), "(");
+    assert_eq!(Token::Ident.pattern(), "[a-z]+");
+    assert_eq!(Token::Number(1).pattern(), "[0-9]+");
+    assert_eq!(Token::Name.pattern(), "\"[a-z]+\" & ! \"let\"");
+    assert_eq!(Token::Eq.pattern(), "== | =");
+    assert_eq!(Token::Error.pattern(), "");
+
+    assert_eq!(ByteToken::Bom.pattern(), "\\xff\\xfe");
+}
+
+#[test]
+fn fixed_strings() {
+    assert_eq!(Token::LParen.fixed_str(), Some("("));
+    assert_eq!(Token::Let.fixed_str(), Some("let"));
+    assert_eq!(Token::Ident.fixed_str(), None);
+    assert_eq!(Token::Eq.fixed_str(), None);
+    assert_eq!(Token::Error.fixed_str(), None);
+
+    assert_eq!(ByteToken::Ab.fixed_str(), Some("ab"));
+    assert_eq!(ByteToken::Bom.fixed_str(), None);
+}
+
+#[test]
+fn all_variants() {
+    assert_eq!(
+        Token::ALL,
+        [Token::LParen, Token::Ident, Token::Name, Token::Let, Token::Eq, Token::Error]
+    );
+
+    for tok in &Token::ALL {
+        assert_eq!(Token::from_index(tok.index()), Some(*tok));
+    }
+}
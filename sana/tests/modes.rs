@@ -0,0 +1,119 @@
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+enum Token {
+    #[regex("[a-z]+")]
+    Ident,
+    #[token("\"", push = string)]
+    Quote,
+    #[token("/*", push = comment)]
+    #[mode(default, comment)]
+    CommentStart,
+    #[regex("[ \n]+")]
+    Whitespace,
+
+    #[regex(r#"[^"\\]+"#)]
+    #[mode(string)]
+    Text,
+    #[regex(r#"\\."#)]
+    #[mode(string)]
+    Escape,
+    #[token("\"", pop)]
+    #[mode(string)]
+    EndQuote,
+
+    #[token("*/", pop)]
+    #[mode(comment)]
+    CommentEnd,
+    #[regex(r"[^*/]+|\*|/")]
+    #[mode(comment)]
+    CommentText,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[backend(vm)]
+enum Header {
+    #[token("#", switch = body)]
+    Hash,
+    #[regex("[a-z]+")]
+    Word,
+
+    #[regex("[a-z]+", priority = 1)]
+    #[mode(body)]
+    Upper,
+    #[token(";", switch = default)]
+    #[mode(body)]
+    End,
+
+    #[error]
+    Error,
+}
+
+fn tokens<'a, T: Sana<'a, Input = str>>(input: &'a str) -> Vec<T> {
+    T::lexer(input)
+        .map(|tok| tok.value)
+        .collect()
+}
+
+#[test]
+fn string_mode() {
+    use Token::*;
+
+    let mut lexer = Token::lexer(r#"x "a b\"c" y"#);
+    assert_eq!(lexer.mode(), "default");
+
+    assert_eq!(lexer.next(), Some(Spanned { value: Ident, start: 0, end: 1 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Whitespace, start: 1, end: 2 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Quote, start: 2, end: 3 }));
+    assert_eq!(lexer.mode(), "string");
+    assert_eq!(lexer.mode_depth(), 2);
+
+    // Whitespace is a part of the text in strings
+    assert_eq!(lexer.next(), Some(Spanned { value: Text, start: 3, end: 6 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Escape, start: 6, end: 8 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Text, start: 8, end: 9 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: EndQuote, start: 9, end: 10 }));
+    assert_eq!(lexer.mode(), "default");
+
+    assert_eq!(lexer.next(), Some(Spanned { value: Whitespace, start: 10, end: 11 }));
+    assert_eq!(lexer.next(), Some(Spanned { value: Ident, start: 11, end: 12 }));
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn nested_comments() {
+    use Token::*;
+
+    assert_eq!(
+        tokens::<Token>("a /* b /* c */ \" */x"),
+        vec![
+            Ident, Whitespace,
+            CommentStart, CommentText, CommentStart, CommentText, CommentEnd,
+            CommentText, CommentEnd,
+            Ident,
+        ]
+    );
+}
+
+#[test]
+fn pop_initial_mode() {
+    let mut lexer = Token::lexer("*/");
+
+    // `*/` is not a token of the default mode
+    assert_eq!(lexer.next().unwrap().value, Token::Error);
+    assert_eq!(lexer.mode_depth(), 1);
+}
+
+#[test]
+fn switch_mode() {
+    use Header::*;
+
+    assert_eq!(
+        tokens::<Header>("ab#cd;ef"),
+        vec![Word, Hash, Upper, End, Word]
+    );
+    assert_eq!(Header::MODES, &["default", "body"]);
+}
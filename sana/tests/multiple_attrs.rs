@@ -15,7 +15,7 @@ fn multiple_attrs() {
     }
 
     let input = "x y";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::Xy, start: 0, end: 1 });
@@ -14,7 +14,7 @@ fn token_break() {
     }
 
     let input = "x y";
-    let mut lexer = Token::lexer(&input);
+    let mut lexer = Token::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: Token::AnyNotSpace, start: 0, end: 1 });
@@ -0,0 +1,57 @@
+//! Rule sets where a match is possible from every state of the DFA
+
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+enum CatchAll {
+    #[regex("[a-z]+", priority = 1)]
+    Word,
+    #[regex("(?s).+")]
+    Rest,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[backend(vm)]
+enum RestVm {
+    #[regex("[a-z]+", priority = 1)]
+    Word,
+    #[regex("(?s).+")]
+    Rest,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(utf8)]
+enum NotDashes {
+    #[regex("(?s).+" & !"--")]
+    Text,
+    #[token("--", priority = 1)]
+    Dashes,
+
+    #[error]
+    Error,
+}
+
+#[test]
+fn catch_all() {
+    let expected = vec![Spanned { value: CatchAll::Rest, start: 0, end: 8 }];
+    assert_eq!(CatchAll::lexer("abc 1\nщ").collect::<Vec<_>>(), expected);
+    assert_eq!(CatchAll::lexer("abc").collect::<Vec<_>>(), vec![Spanned { value: CatchAll::Word, start: 0, end: 3 }]);
+
+    let expected = vec![Spanned { value: RestVm::Rest, start: 0, end: 8 }];
+    assert_eq!(RestVm::lexer("abc 1\nщ").collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn complement() {
+    let tokens: Vec<_> = NotDashes::lexer("--").collect();
+    assert_eq!(tokens, vec![Spanned { value: NotDashes::Dashes, start: 0, end: 2 }]);
+
+    let tokens: Vec<_> = NotDashes::lexer("-- ж").collect();
+    assert_eq!(tokens, vec![Spanned { value: NotDashes::Text, start: 0, end: 5 }]);
+}
@@ -0,0 +1,80 @@
+//! The optimized IR must produce the same tokens as the unoptimized one
+
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+enum Token {
+    #[regex("[a-z_][a-z0-9_]*")]
+    Ident,
+    #[token("let", priority = 1)]
+    Let,
+    #[regex("[0-9]+")]
+    Int,
+    #[regex("[0-9]+\\.[0-9]+")]
+    Float,
+    #[regex(r#""([^"\\]|\\.)*""#)]
+    Str,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+#[sana(no_optimize)]
+enum Plain {
+    #[regex("[a-z_][a-z0-9_]*")]
+    Ident,
+    #[token("let", priority = 1)]
+    Let,
+    #[regex("[0-9]+")]
+    Int,
+    #[regex("[0-9]+\\.[0-9]+")]
+    Float,
+    #[regex(r#""([^"\\]|\\.)*""#)]
+    Str,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+#[backend(vm)]
+#[sana(no_optimize(thread_jumps, coalesce_ranges))]
+enum PlainVm {
+    #[regex("[a-z_][a-z0-9_]*")]
+    Ident,
+    #[token("let", priority = 1)]
+    Let,
+    #[regex("[0-9]+")]
+    Int,
+    #[regex("[0-9]+\\.[0-9]+")]
+    Float,
+    #[regex(r#""([^"\\]|\\.)*""#)]
+    Str,
+
+    #[error]
+    Error,
+}
+
+// An error at the start of a token is repeated, so the number of the tokens
+// is limited
+fn tokens<T>(lexer: impl Iterator<Item = Spanned<T>>, index: fn(&T) -> usize)
+    -> Vec<(usize, usize, usize)>
+{
+    lexer.take(16).map(|t| (index(&t.value), t.start, t.end)).collect()
+}
+
+#[test]
+fn no_optimize() {
+    let inputs = ["let x = 1.5", "lets 12. \"a\\\"b\" \"open", "", "ж"];
+
+    for input in inputs {
+        let expected = tokens(Token::lexer(input), Token::index);
+
+        assert_eq!(tokens(Plain::lexer(input), Plain::index), expected, "{:?}", input);
+        assert_eq!(tokens(PlainVm::lexer(input), PlainVm::index), expected, "{:?}", input);
+    }
+}
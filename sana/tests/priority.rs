@@ -7,7 +7,7 @@ fn priority_first() {
     enum AmbigToken {
         #[regex(".+", priority = 1)]
         A,
-        #[regex(".+")] // default priority = 0
+        #[regex(".+", allow_unreachable)] // default priority = 0
         #[allow(dead_code)]
         B, // this one should not be constructed
 
@@ -16,7 +16,7 @@ fn priority_first() {
     }
 
     let input = "x";
-    let mut lexer = AmbigToken::lexer(&input);
+    let mut lexer = AmbigToken::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: AmbigToken::A, start: 0, end: 1 });
@@ -29,7 +29,7 @@ fn priority_last() {
     // the last regex has the highest priority
     #[derive(Clone, Copy, Sana, PartialEq, Debug)]
     enum AmbigToken {
-        #[regex(".+")] // default priority = 0
+        #[regex(".+", allow_unreachable)] // default priority = 0
         #[allow(dead_code)]
         A, // this one should not be constructed
         #[regex(".+", priority = 1)]
@@ -40,7 +40,7 @@ fn priority_last() {
     }
 
     let input = "x";
-    let mut lexer = AmbigToken::lexer(&input);
+    let mut lexer = AmbigToken::lexer(input);
 
     let tok = lexer.next().unwrap();
     assert_eq!(tok, Spanned{ value: AmbigToken::B, start: 0, end: 1 });
@@ -0,0 +1,115 @@
+use sana::{Sana, Spanned};
+
+#[test]
+fn exact() {
+    #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+    enum Token {
+        #[regex(r"\d{4}-\d{2}-\d{2}")]
+        Date,
+        #[regex(r"\d+")]
+        Number,
+        #[token(" ")]
+        Space,
+
+        #[error]
+        Error,
+    }
+
+    let input = "2021-01-31 2021-1-31";
+    let mut lexer = Token::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Date, start: 0, end: 10 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Space, start: 10, end: 11 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Number, start: 11, end: 15 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Error, start: 15, end: 15 });
+}
+
+#[test]
+fn bounded() {
+    #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+    enum Token {
+        #[regex("x{2,3}")]
+        Xs,
+        #[token("x")]
+        X,
+
+        #[error]
+        Error,
+    }
+
+    let input = "xxxxxx";
+    let mut lexer = Token::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Xs, start: 0, end: 3 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Xs, start: 3, end: 6 });
+
+    assert!(lexer.next().is_none());
+
+    let input = "xxxx";
+    let mut lexer = Token::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Xs, start: 0, end: 3 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::X, start: 3, end: 4 });
+
+    assert!(lexer.next().is_none());
+}
+
+#[test]
+fn at_least() {
+    #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+    enum Token {
+        #[regex("(ab){2,}")]
+        Abs,
+        #[token(" ")]
+        Space,
+
+        #[error]
+        Error,
+    }
+
+    let input = "abababab ab ";
+    let mut lexer = Token::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Abs, start: 0, end: 8 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Space, start: 8, end: 9 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Error, start: 9, end: 11 });
+}
+
+#[test]
+fn unicode_escape() {
+    #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+    enum Token {
+        #[regex(r"\\u[0-9a-fA-F]{4}")]
+        Escape,
+
+        #[error]
+        Error,
+    }
+
+    let input = r"\u00E9\u00EG";
+    let mut lexer = Token::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: Token::Escape, start: 0, end: 6 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok.value, Token::Error);
+}
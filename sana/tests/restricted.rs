@@ -0,0 +1,149 @@
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+#[sana(restricted)]
+enum Token {
+    #[regex("[a-z]+")]
+    Ident,
+    #[token("/")]
+    Div,
+    #[regex("/[^/]*/")]
+    Regex,
+    #[token(">")]
+    Greater,
+    #[token(">>")]
+    Shift,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[backend(vm)]
+#[sana(restricted)]
+enum Template {
+    #[token("{{", push = code)]
+    Open,
+    #[regex("[^{]+")]
+    Text,
+
+    #[token("}}", pop)]
+    #[mode(code)]
+    Close,
+    #[token("}")]
+    #[mode(code)]
+    Brace,
+    #[regex("[a-z]+")]
+    #[mode(code)]
+    Word,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(restricted)]
+enum Number {
+    #[regex("[0-9]+\\.[0-9]+")]
+    Float,
+    #[token(" ")]
+    Space,
+
+    #[error]
+    Error,
+}
+
+#[test]
+fn division_or_regex() {
+    let operand: <Token as Sana>::Set = [Token::Ident, Token::Regex].iter().copied().collect();
+    let operator: <Token as Sana>::Set = [Token::Div, Token::Greater, Token::Shift].iter().copied().collect();
+
+    let mut lexer = Token::lexer("a / b / c");
+    assert_eq!(lexer.next_restricted(&operand).map(|t| t.value), Some(Token::Ident));
+    assert_eq!(
+        lexer.next_restricted(&operator),
+        Some(Spanned { value: Token::Div, start: 2, end: 3 })
+    );
+
+    let mut lexer = Token::lexer("/ b / c");
+    assert_eq!(
+        lexer.next_restricted(&operand),
+        Some(Spanned { value: Token::Regex, start: 0, end: 5 })
+    );
+    assert_eq!(lexer.next_restricted(&operand).map(|t| t.value), Some(Token::Ident));
+    assert_eq!(lexer.next_restricted(&operand), None);
+}
+
+#[test]
+fn shorter_match() {
+    let mut greater = <Token as Sana>::Set::new();
+    greater.insert(&Token::Greater);
+
+    let mut lexer = Token::lexer(">>");
+    assert_eq!(lexer.next_restricted(&greater), Some(Spanned { value: Token::Greater, start: 0, end: 1 }));
+    assert_eq!(lexer.next_restricted(&greater), Some(Spanned { value: Token::Greater, start: 1, end: 2 }));
+
+    let mut lexer = Token::lexer(">>");
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Shift, start: 0, end: 2 }));
+}
+
+#[test]
+fn nothing_allowed() {
+    let mut lexer = Token::lexer("abc >");
+
+    assert_eq!(lexer.next_restricted(&<Token as Sana>::Set::new()).map(|t| t.value), Some(Token::Error));
+
+    // Reaching the end of input without a match is not an error, as for `next`
+    let mut lexer = Token::lexer("abc");
+
+    assert_eq!(lexer.next_restricted(&<Token as Sana>::Set::new()), None);
+}
+
+#[test]
+fn modes() {
+    let mut set: <Template as Sana>::Set = [Template::Open, Template::Close, Template::Word].iter().copied().collect();
+
+    let mut lexer = Template::lexer("{{a}}}");
+    assert_eq!(lexer.next_restricted(&set).map(|t| t.value), Some(Template::Open));
+    assert_eq!(lexer.mode(), "code");
+    assert_eq!(lexer.next_restricted(&set).map(|t| t.value), Some(Template::Word));
+
+    set.insert(&Template::Brace);
+    assert!(set.contains(&Template::Brace));
+    assert!(!set.contains(&Template::Text));
+
+    assert_eq!(lexer.next_restricted(&set).map(|t| t.value), Some(Template::Close));
+    assert_eq!(lexer.mode(), "default");
+}
+
+#[test]
+fn same_as_unrestricted() {
+    let all: <Token as Sana>::Set = [Token::Ident, Token::Div, Token::Regex, Token::Greater, Token::Shift]
+        .iter().copied().collect();
+
+    let input = "a / b /c/ >> > x";
+    let expected: Vec<_> = Token::lexer(input).collect();
+
+    let mut lexer = Token::lexer(input);
+    let tokens: Vec<_> = std::iter::from_fn(|| lexer.next_restricted(&all)).collect();
+
+    assert_eq!(tokens, expected);
+}
+
+#[test]
+fn partial_token_at_end() {
+    let all: <Number as Sana>::Set = [Number::Float, Number::Space].iter().copied().collect();
+
+    for input in ["1.", "1.5 2", "1.5 2."] {
+        let expected: Vec<_> = Number::lexer(input).collect();
+
+        let mut lexer = Number::lexer(input);
+        let tokens: Vec<_> = std::iter::from_fn(|| lexer.next_restricted(&all)).collect();
+
+        assert_eq!(tokens, expected, "{:?}", input);
+    }
+
+    let mut lexer = Number::lexer("1.");
+    assert_eq!(lexer.next_restricted(&all), None);
+}
@@ -0,0 +1,99 @@
+use sana::{Sana, Spanned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip("[ \t\r\n]+")]
+#[skip(r"//[^\n]*")]
+enum Token {
+    #[regex("[a-z]+")]
+    Ident,
+    #[token("/")]
+    Slash,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[backend(vm)]
+#[skip(" +")]
+enum VmToken {
+    #[regex("[a-z]+")]
+    Ident,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +", mode = default, mode = list)]
+enum ModeToken {
+    #[token("[", push = list)]
+    #[mode(default, list)]
+    Open,
+    #[regex("[a-z]+")]
+    Word,
+
+    #[token("]", pop)]
+    #[mode(list)]
+    Close,
+    #[regex("[0-9]+")]
+    #[mode(list)]
+    Number,
+    #[token(",")]
+    #[mode(list)]
+    Comma,
+
+    #[error]
+    Error,
+}
+
+#[test]
+fn skip_whitespace_and_comments() {
+    let tokens: Vec<_> = Token::lexer("a / b // comment\n  c").collect();
+
+    assert_eq!(tokens, vec![
+        Spanned { value: Token::Ident, start: 0, end: 1 },
+        Spanned { value: Token::Slash, start: 2, end: 3 },
+        Spanned { value: Token::Ident, start: 4, end: 5 },
+        Spanned { value: Token::Ident, start: 19, end: 20 },
+    ]);
+}
+
+#[test]
+fn skip_trailing() {
+    let tokens: Vec<_> = Token::lexer("a  // trailing comment").collect();
+
+    assert_eq!(tokens, vec![
+        Spanned { value: Token::Ident, start: 0, end: 1 },
+    ]);
+
+    assert_eq!(Token::lexer("   ").next(), None);
+}
+
+#[test]
+fn skip_before_error() {
+    let mut lexer = Token::lexer("  ?");
+
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Error, start: 2, end: 2 }));
+}
+
+#[test]
+fn skip_vm() {
+    let tokens: Vec<_> = VmToken::lexer("  ab cd ").collect();
+
+    assert_eq!(tokens, vec![
+        Spanned { value: VmToken::Ident, start: 2, end: 4 },
+        Spanned { value: VmToken::Ident, start: 5, end: 7 },
+    ]);
+}
+
+#[test]
+fn skip_in_modes() {
+    use ModeToken::*;
+
+    let tokens: Vec<_> = ModeToken::lexer("a [ 1 , [2] ] b")
+        .map(|tok| tok.value)
+        .collect();
+
+    assert_eq!(tokens, vec![Word, Open, Number, Comma, Open, Number, Close, Close, Word]);
+}
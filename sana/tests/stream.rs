@@ -0,0 +1,161 @@
+use sana::{Sana, Spanned};
+use sana::stream::StreamLexer;
+
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip("[ \n]+")]
+enum Token {
+    #[regex("[a-zа-я]+")]
+    Ident,
+    #[regex("[0-9]+(\\.[0-9]+)?")]
+    Number,
+    #[token("\"", push = string)]
+    Quote,
+
+    #[regex(r#"[^"]+"#)]
+    #[mode(string)]
+    Text,
+    #[token("\"", pop)]
+    #[mode(string)]
+    EndQuote,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(bytes)]
+#[backend(vm)]
+enum ByteToken {
+    #[token("a")]
+    A,
+    #[token("abc")]
+    Abc,
+    #[regex("(?-u:[\\x80-\\xff])+")]
+    High,
+
+    #[error]
+    Error,
+}
+
+/// A reader that returns at most `max` bytes at once
+struct Trickle<'a> {
+    data: &'a [u8],
+    max: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.max).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+
+        Ok(len)
+    }
+}
+
+fn stream<T: sana::stream::StreamSana>(data: &[u8], max: usize, chunk: usize) -> Vec<Spanned<T>> {
+    let reader = Trickle { data, max };
+
+    StreamLexer::<_, T>::with_chunk_size(reader, chunk)
+        .collect::<io::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn same_as_lexer() {
+    let input = "let x \"a long string\"\n12.5 привет 7 \"\" y";
+    let expected: Vec<_> = Token::lexer(input).collect();
+
+    for &max in &[1, 2, 3, 7, 100] {
+        for &chunk in &[1, 2, 5, 64] {
+            assert_eq!(stream::<Token>(input.as_bytes(), max, chunk), expected, "max = {}, chunk = {}", max, chunk);
+        }
+    }
+}
+
+#[test]
+fn backtracking() {
+    let expected = vec![
+        Spanned { value: ByteToken::A, start: 0, end: 1 },
+        Spanned { value: ByteToken::Abc, start: 1, end: 4 },
+        Spanned { value: ByteToken::High, start: 4, end: 7 },
+        Spanned { value: ByteToken::A, start: 7, end: 8 },
+    ];
+
+    for &max in &[1, 2, 3] {
+        assert_eq!(stream::<ByteToken>(b"aabc\xff\xfe\xfda", max, 1), expected);
+    }
+}
+
+#[test]
+fn slices() {
+    let reader = Trickle { data: "ab 12.75 щ".as_bytes(), max: 2 };
+    let mut lexer = StreamLexer::<_, Token>::with_chunk_size(reader, 1);
+
+    let mut slices = vec![];
+    while let Some(tok) = lexer.next() {
+        tok.unwrap();
+        slices.push(lexer.slice().to_string());
+    }
+
+    assert_eq!(slices, vec!["ab", "12.75", "щ"]);
+    assert_eq!(lexer.position(), 11);
+}
+
+#[test]
+fn many_short_tokens() {
+    // Each token must not decode the whole buffer again
+    let input = "a ".repeat(200_000);
+    let reader = Trickle { data: input.as_bytes(), max: usize::MAX };
+    let lexer = StreamLexer::<_, Token>::with_chunk_size(reader, 1 << 20);
+
+    let count = lexer.map(Result::unwrap).count();
+    assert_eq!(count, 200_000);
+}
+
+#[test]
+fn invalid_utf8() {
+    let reader = Trickle { data: b"ab \xff", max: 1 };
+    let mut lexer = StreamLexer::<_, Token>::new(reader);
+
+    let err = lexer.find_map(Result::err).unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let reader = Trickle { data: b"ab \xd0", max: 1 };
+    let mut lexer = StreamLexer::<_, Token>::new(reader);
+
+    let err = lexer.find_map(Result::err).unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn modes() {
+    let reader = Trickle { data: b"a \"b c\" d", max: 3 };
+    let mut lexer = StreamLexer::<_, Token>::with_chunk_size(reader, 2);
+
+    assert_eq!(lexer.mode(), "default");
+    lexer.next();
+    lexer.next();
+    assert_eq!(lexer.mode(), "string");
+    lexer.next();
+    lexer.next();
+    assert_eq!(lexer.mode(), "default");
+}
+
+#[test]
+fn read_error() {
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken"))
+        }
+    }
+
+    let mut lexer = StreamLexer::<_, Token>::new(io::Read::chain(&b"ab "[..], Failing));
+
+    assert_eq!(lexer.next().unwrap().unwrap(), Spanned { value: Token::Ident, start: 0, end: 2 });
+    assert_eq!(lexer.next().unwrap().unwrap_err().kind(), io::ErrorKind::Other);
+}
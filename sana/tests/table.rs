@@ -0,0 +1,140 @@
+//! The table backend must produce the same tokens as the generated code
+
+use sana::{Sana, Spanned};
+
+macro_rules! lexers {
+    ($(#[$attr:meta])* enum $rust:ident, $table:ident { $($body:tt)* }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+        $(#[$attr])*
+        enum $rust { $($body)* }
+
+        #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+        #[backend(table)]
+        $(#[$attr])*
+        enum $table { $($body)* }
+    };
+}
+
+lexers! {
+    #[skip(" +")]
+    enum Token, TableToken {
+        #[regex("[a-zA-Z_][a-zA-Z0-9_]*")]
+        Ident,
+        #[token("let", priority = 1)]
+        Let,
+        #[regex("[0-9]+")]
+        Int,
+        #[regex("[0-9]+\\.[0-9]+")]
+        Float,
+        #[regex("[a-z]+", followed_by = ":", priority = 1)]
+        Label,
+        #[token(":")]
+        Colon,
+        #[token("\"", push = string)]
+        Quote,
+
+        #[regex(r#"[^"\\]+"#)]
+        #[mode(string)]
+        Text,
+        #[regex(r#"\\."#)]
+        #[mode(string)]
+        Escape,
+        #[token("\"", pop)]
+        #[mode(string)]
+        EndQuote,
+
+        #[error]
+        Error,
+    }
+}
+
+lexers! {
+    #[sana(utf8)]
+    enum Utf8Token, TableUtf8Token {
+        #[regex("[а-яё]+")]
+        Word,
+        #[regex("[\u{1F600}-\u{1F64F}]+")]
+        Emoji,
+        #[token(" ")]
+        Space,
+
+        #[error]
+        Error,
+    }
+}
+
+lexers! {
+    #[sana(bytes)]
+    enum ByteToken, TableByteToken {
+        #[regex("[a-z]+")]
+        Word,
+        #[regex(r"(?-u)[\x80-\xFF]+")]
+        High,
+        #[token(b"\x00\x01")]
+        Magic,
+
+        #[error]
+        Error,
+    }
+}
+
+// An error at the start of a token is repeated, so the number of the tokens
+// is limited
+fn tokens<T>(lexer: impl Iterator<Item = Spanned<T>>, index: fn(&T) -> usize)
+    -> Vec<(usize, usize, usize)>
+{
+    lexer.take(16).map(|t| (index(&t.value), t.start, t.end)).collect()
+}
+
+fn compare(input: &str) {
+    assert_eq!(
+        tokens(Token::lexer(input), Token::index),
+        tokens(TableToken::lexer(input), TableToken::index),
+        "{:?}", input,
+    );
+}
+
+#[test]
+fn same_as_rust() {
+    compare("");
+    compare("let x = 1.5");
+    compare("lets: 12. foo");
+    compare("label: \"a\\\"b\" let");
+    compare("\"unterminated");
+    compare("123abc 4.");
+    compare("ж x");
+}
+
+#[test]
+fn table_tokens() {
+    let tokens: Vec<_> = TableToken::lexer("let x: 1.5").collect();
+    assert_eq!(tokens, vec![
+        Spanned { value: TableToken::Let, start: 0, end: 3 },
+        Spanned { value: TableToken::Label, start: 4, end: 5 },
+        Spanned { value: TableToken::Colon, start: 5, end: 6 },
+        Spanned { value: TableToken::Float, start: 7, end: 10 },
+    ]);
+}
+
+#[test]
+fn utf8() {
+    for input in ["ёжик 😀😁 ж", "😀x", "", "x"] {
+        assert_eq!(
+            tokens(Utf8Token::lexer(input), Utf8Token::index),
+            tokens(TableUtf8Token::lexer(input), TableUtf8Token::index),
+            "{:?}", input,
+        );
+    }
+}
+
+#[test]
+fn bytes() {
+    let inputs: [&[u8]; 3] = [b"abc\xE9\xFF\x00\x01x", b"\x00\x02", b"\x00"];
+    for input in inputs {
+        assert_eq!(
+            tokens(ByteToken::lexer(input), ByteToken::index),
+            tokens(TableByteToken::lexer(input), TableByteToken::index),
+            "{:?}", input,
+        );
+    }
+}
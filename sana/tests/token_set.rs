@@ -0,0 +1,86 @@
+use sana::{Sana, TokenSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+#[sana(set = Tokens)]
+enum Token {
+    #[token("(")]
+    LParen,
+    #[token(")")]
+    RParen,
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("[0-9]+", parse = parse_number)]
+    Number(u32),
+    #[token("+")]
+    Plus,
+
+    #[error]
+    Error,
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    s.parse().ok()
+}
+
+const FIRST: Tokens = Tokens::new()
+    .with(Token::LParen.index())
+    .with(Token::Ident.index())
+    .with(Token::Number(0).index());
+
+const FOLLOW: Tokens = Tokens::new()
+    .with(Token::RParen.index())
+    .with(Token::Plus.index());
+
+const ALL: Tokens = FIRST.union(FOLLOW);
+
+#[test]
+fn indices() {
+    assert_eq!(Token::COUNT, 6);
+    assert_eq!(Token::LParen.index(), 0);
+    assert_eq!(Token::Error.index(), 5);
+
+    assert_eq!(Token::from_index(1), Some(Token::RParen));
+    assert_eq!(Token::from_index(5), Some(Token::Error));
+    assert_eq!(Token::from_index(3), None);
+    assert_eq!(Token::from_index(Token::COUNT), None);
+}
+
+#[test]
+fn const_sets() {
+    assert!(FIRST.contains(&Token::Number(7)));
+    assert!(!FIRST.contains(&Token::Plus));
+    assert_eq!(ALL.len(), 5);
+    assert!(!ALL.contains(&Token::Error));
+
+    assert!(FIRST.intersection(FOLLOW).is_empty());
+    assert_eq!(ALL.difference(FOLLOW), FIRST);
+    assert_eq!(ALL.indices().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn insert_and_remove() {
+    // The derive does not shadow `sana::TokenSet`
+    let mut set: TokenSet<Token, 1> = TokenSet::new();
+    set.insert(&Token::Plus);
+    set.insert(&Token::Error);
+    set.remove(&Token::Plus);
+
+    assert_eq!(set, Tokens::new().with(Token::Error.index()));
+    assert_eq!(format!("{:?}", set), "{5}");
+}
+
+// The set type is also available without an alias
+const PARENS: <Token as Sana>::Set = <Token as Sana>::Set::new()
+    .with(Token::LParen.index())
+    .with(Token::RParen.index());
+
+fn collect<'s, T: Sana<'s>>(tokens: &[T]) -> T::Set {
+    tokens.iter().cloned().collect()
+}
+
+#[test]
+fn associated_type() {
+    assert_eq!(collect(&[Token::LParen, Token::RParen]), PARENS);
+    assert_eq!(collect::<Token>(&[]), Default::default());
+}
@@ -0,0 +1,125 @@
+use sana::{Sana, Spanned};
+use sana::dynamic::{DynamicSana, Error, Regex, Rule, RuleSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[skip(" +")]
+#[sana(restricted)]
+enum Token {
+    #[regex("[a-z]+")]
+    Ident,
+    #[regex("[a-z]+", followed_by = ":")]
+    Label,
+    #[regex("[0-9]+")]
+    Int,
+    #[regex("[0-9]+\\.[0-9]+")]
+    Float,
+    #[regex("[0-9]+\\.", followed_by = "[^.0-9]")]
+    Dotted,
+    #[token("..")]
+    Range,
+    #[token(":")]
+    Colon,
+    #[token("if", followed_by = "[^a-z]", priority = 1)]
+    If,
+
+    #[error]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Sana)]
+#[sana(utf8)]
+enum Utf8Token {
+    #[regex("[а-я]+", followed_by = "[ёж]ж")]
+    Word,
+    #[regex("[а-яё]")]
+    Letter,
+
+    #[error]
+    Error,
+}
+
+fn values<'s, T: Sana<'s, Input = str> + Copy>(input: &'s str) -> Vec<(T, &'s str)> {
+    let mut lexer = T::lexer(input);
+
+    std::iter::from_fn(|| lexer.next().map(|t| (t.value, &input[t.start..t.end]))).collect()
+}
+
+#[test]
+fn label() {
+    assert_eq!(values::<Token>("foo: bar"), vec![
+        (Token::Label, "foo"),
+        (Token::Colon, ":"),
+        (Token::Ident, "bar"),
+    ]);
+}
+
+#[test]
+fn range() {
+    assert_eq!(values::<Token>("1..2 3. 4.5"), vec![
+        (Token::Int, "1"),
+        (Token::Range, ".."),
+        (Token::Int, "2"),
+        (Token::Dotted, "3."),
+        (Token::Float, "4.5"),
+    ]);
+}
+
+#[test]
+fn keyword() {
+    assert_eq!(values::<Token>("if iff if:"), vec![
+        (Token::If, "if"),
+        (Token::Ident, "iff"),
+        (Token::If, "if"),
+        (Token::Colon, ":"),
+    ]);
+}
+
+#[test]
+fn context_is_not_consumed() {
+    let mut lexer = Token::lexer("ab:");
+    assert_eq!(lexer.next(), Some(Spanned { value: Token::Label, start: 0, end: 2 }));
+    assert_eq!(lexer.position(), 2);
+}
+
+#[test]
+fn multibyte_context() {
+    assert_eq!(values::<Utf8Token>("абёж"), vec![
+        (Utf8Token::Word, "аб"),
+        (Utf8Token::Letter, "ё"),
+        (Utf8Token::Letter, "ж"),
+    ]);
+}
+
+#[test]
+fn restricted() {
+    let set: <Token as Sana>::Set = [Token::Label, Token::Colon].iter().copied().collect();
+    let mut lexer = Token::lexer("ab:");
+
+    assert_eq!(lexer.next_restricted(&set), Some(Spanned { value: Token::Label, start: 0, end: 2 }));
+    assert_eq!(lexer.next_restricted(&set), Some(Spanned { value: Token::Colon, start: 2, end: 3 }));
+}
+
+#[test]
+fn dynamic() {
+    let rules = RuleSet {
+        rules: vec![
+            Rule::new(Regex::parse("\"[a-z]+\"").unwrap(), 0, "ident"),
+            Rule::new(Regex::parse("\"[a-z]+\"").unwrap(), 0, "call"),
+            Rule::new(Regex::literal_str("("), 0, "paren"),
+        ],
+    };
+    let trailing = [None, Some(Regex::literal_str("("))];
+    let sana = DynamicSana::with_trailing(&rules, &trailing, "error").unwrap();
+    let tokens: Vec<_> = sana.lexer("f(x").map(|t| t.value).collect();
+
+    assert_eq!(tokens, vec!["call", "paren", "ident"]);
+
+    let trailing = [Some(Regex::parse("\"x+\"").unwrap())];
+    assert_eq!(DynamicSana::with_trailing(&rules, &trailing, "error").unwrap_err(), Error::Trailing(0));
+}
+
+#[test]
+fn patterns() {
+    assert_eq!(Token::Label.pattern(), "[a-z]+/:");
+    assert_eq!(Token::If.fixed_str(), Some("if"));
+}
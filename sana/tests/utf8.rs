@@ -0,0 +1,83 @@
+use sana::{Sana, Spanned};
+
+macro_rules! tokens {
+    ($name:ident $(, $attr:meta)*) => {
+        #[derive(Clone, Copy, Sana, PartialEq, Debug)]
+        $(#[$attr])*
+        enum $name {
+            #[regex("[a-zA-Zα-ω_][a-zA-Zα-ω0-9_]*")]
+            Ident,
+            #[token("é")]
+            ECute,
+            #[token("🦀", priority = 1)]
+            Crab,
+            #[regex(r"\p{So}+")]
+            Symbols,
+            #[regex("[ \t\r\n]+")]
+            Whitespace,
+
+            #[error]
+            Error,
+        }
+    };
+}
+
+tokens!(CharToken);
+tokens!(RustToken, sana(utf8));
+tokens!(VmToken, sana(utf8), backend(vm));
+
+const INPUT: &str = "λx 🦀 é☃☃ è_ 🦀🦀é\n😀x";
+
+fn spans<'a, T: Sana<'a>>(input: &'a T::Input) -> Vec<(usize, usize)> {
+    T::lexer(input)
+        .map(|Spanned { start, end, .. }| (start, end))
+        .take(32)
+        .collect()
+}
+
+#[test]
+fn same_spans() {
+    let gold = spans::<CharToken>(INPUT);
+
+    assert_eq!(spans::<RustToken>(INPUT), gold);
+    assert_eq!(spans::<VmToken>(INPUT), gold);
+}
+
+#[test]
+fn error_at_char_boundary() {
+    let input = "éè";
+    let mut lexer = RustToken::lexer(input);
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: RustToken::ECute, start: 0, end: 2 });
+
+    // `è` shares the first byte with `é`
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: RustToken::Error, start: 2, end: 2 });
+    assert_eq!(lexer.position(), 2);
+
+    let mut lexer = VmToken::lexer(input);
+    lexer.next();
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: VmToken::Error, start: 2, end: 2 });
+}
+
+#[test]
+fn tokens() {
+    let mut lexer = RustToken::lexer("🦀 ☃λ");
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: RustToken::Crab, start: 0, end: 4 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: RustToken::Whitespace, start: 4, end: 5 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: RustToken::Symbols, start: 5, end: 8 });
+
+    let tok = lexer.next().unwrap();
+    assert_eq!(tok, Spanned{ value: RustToken::Ident, start: 8, end: 10 });
+
+    assert!(lexer.next().is_none());
+}
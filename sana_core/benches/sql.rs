@@ -3,7 +3,7 @@ use sana_core::{Rule, RuleSet, regex::Regex};
 
 use std::convert::TryFrom;
 
-pub fn sql_dfa(c: &mut Criterion) {
+fn sql_rules() -> RuleSet<&'static str> {
     let rules: Vec<_> = keywords().iter()
         .map(|(regex, act)|  {
             let hir = regex_syntax::Parser::new()
@@ -18,7 +18,11 @@ pub fn sql_dfa(c: &mut Criterion) {
         })
         .collect();
 
-    let ruleset = RuleSet { rules };
+    RuleSet { rules }
+}
+
+pub fn sql_dfa(c: &mut Criterion) {
+    let ruleset = sql_rules();
 
     c.bench_function(
         "SQL DFA construction",
@@ -26,7 +30,16 @@ pub fn sql_dfa(c: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, sql_dfa);
+pub fn sql_minimize(c: &mut Criterion) {
+    let dfa = sql_rules().construct_dfa().unwrap();
+
+    c.bench_function(
+        "SQL DFA minimization",
+        |b| b.iter(|| black_box(&dfa).minimize())
+    );
+}
+
+criterion_group!(benches, sql_dfa, sql_minimize);
 criterion_main!(benches);
 
 pub fn keywords() -> &'static [(&'static str, &'static str)] {
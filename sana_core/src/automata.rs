@@ -1,6 +1,8 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::ops::RangeInclusive;
 
+use utf8_ranges::Utf8Sequences;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum State<T> {
     Normal,
@@ -8,7 +10,7 @@ pub enum State<T> {
 }
 
 /// Inclusive char range
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CharRange {
     pub start: char,
     pub end: char,
@@ -18,12 +20,12 @@ pub struct CharRange {
 ///
 /// - A sink node is a node with more than one arrow pointing to the node
 /// - A fork node is a non-sink node with more than one arrow pointing
-/// from the node
+///   from the node
 /// - A link node is a non-sink node with only one transition from it
-/// besides the the terminal arrow
+///   besides the the terminal arrow
 /// - A leaf node is a non-sink node with only terminal transition
 /// - A terminal node is a node such as the only transition from it
-/// is a full range loop
+///   is a full range loop
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum NodeKind {
     Fork,
@@ -36,6 +38,8 @@ pub enum NodeKind {
 impl CharRange {
     pub const MIN: Self = CharRange { start: '\0', end: '\0' };
     pub const MAX: Self = CharRange { start: std::char::MAX, end: std::char::MAX };
+    /// The range of all chars
+    pub const FULL: Self = CharRange { start: '\0', end: std::char::MAX };
 
     pub fn new(start: char, end: char) -> Self {
         assert!(start <= end);
@@ -65,6 +69,56 @@ impl CharRange {
     }
 }
 
+/// A partition of chars into equivalence classes
+///
+/// The chars of one class lead to the same state from every state of the
+/// automata, so a transition can be looked up by the class of a char
+/// instead of by its range. The classes are numbered in the order of their
+/// first chars, so the class of `'\0'` is `0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassMap {
+    /// The classes of the ASCII chars
+    pub ascii: [usize; 128],
+    /// Sorted disjoint ranges with their classes, covering all non-ASCII
+    /// chars
+    pub ranges: Vec<(CharRange, usize)>,
+    /// The first char of each class
+    pub representatives: Vec<char>,
+}
+
+impl ClassMap {
+    /// The class of the given char
+    pub fn class(&self, ch: char) -> usize {
+        if ch.is_ascii() {
+            return self.ascii[ch as usize]
+        }
+
+        let i = self.ranges.partition_point(|(range, _)| range.end < ch);
+        self.ranges[i].1
+    }
+
+    /// The number of the classes
+    pub fn len(&self) -> usize {
+        self.representatives.len()
+    }
+
+    /// Return `true` if there are no classes, which never happens for an
+    /// automata, as every char belongs to a class
+    pub fn is_empty(&self) -> bool {
+        self.representatives.is_empty()
+    }
+
+    /// The ranges of all chars with their classes, adjacent ranges of the
+    /// same class are merged
+    pub fn char_ranges(&self) -> Vec<(CharRange, usize)> {
+        let ascii = self.ascii.iter()
+            .enumerate()
+            .map(|(ch, &class)| (CharRange::new(ch as u8 as char, ch as u8 as char), class));
+
+        merge_transitions(ascii.chain(self.ranges.iter().copied()))
+    }
+}
+
 fn state_range(state: usize) -> RangeInclusive<(usize, CharRange)> {
     let min = CharRange::MIN;
     let max = CharRange::MAX;
@@ -119,26 +173,111 @@ impl<T> Automata<T> {
             .map(|(_, state)| state)
     }
 
+    /// Find the states from which an action state is reachable
+    ///
+    /// The indices of the list match the indices of the states
+    pub fn live_states(&self) -> Vec<bool> {
+        let mut live: Vec<_> = self.states.iter()
+            .map(|st| matches!(st, State::Action(_)))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (&(from, _), &to) in &self.edges {
+                if live[to] && !live[from] {
+                    live[from] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        live
+    }
+
+    /// The length of all strings accepted by the automata
+    ///
+    /// Return `None` if the automata accepts strings of different lengths
+    /// or does not accept anything
+    pub fn fixed_length(&self) -> Option<usize> {
+        let live = self.live_states();
+        if !live[0] { return None }
+
+        // All paths to a live state must have the same length
+        let mut depths = vec![None; self.states.len()];
+        let mut queue = VecDeque::new();
+        depths[0] = Some(0);
+        queue.push_back(0);
+
+        while let Some(st) = queue.pop_front() {
+            let depth = depths[st].unwrap();
+
+            for (_, to) in self.transitions_from(st) {
+                if !live[to] { continue }
+
+                match depths[to] {
+                    Some(d) if d != depth + 1 => return None,
+                    Some(_) => (),
+                    None => {
+                        depths[to] = Some(depth + 1);
+                        queue.push_back(to);
+                    },
+                }
+            }
+        }
+
+        let mut lengths = self.states.iter()
+            .zip(depths)
+            .filter(|(st, _)| matches!(st, State::Action(_)))
+            .filter_map(|(_, depth)| depth);
+        let length = lengths.next()?;
+
+        if lengths.all(|l| l == length) { Some(length) }
+        else { None }
+    }
+
     /// Find the terminal node of the automata
+    ///
+    /// Panics if the automata has no terminal node, see
+    /// `try_find_terminal_node`
     pub fn find_terminal_node(&self) -> usize {
-        for i in 0..self.states.len() {
-            let ends: Vec<_> = self.transitions_from(i)
-                .filter(|(&ch, _)| ch.start == '\0' && ch.end == std::char::MAX)
-                .map(|(_, end)| end)
-                .collect();
+        self.try_find_terminal_node()
+            .expect("Automata without a terminal state")
+    }
 
-            if ends.len() == 1 { return ends[0] }
+    /// Find the terminal node of the automata, if any
+    ///
+    /// The terminal node is a normal state such as the only transition from it
+    /// is a full range loop, so no match is possible after reaching it. Return
+    /// `None` if there is no such state, which happens when a match is
+    /// possible from every state, as in the automata of `(?s).+`
+    pub fn try_find_terminal_node(&self) -> Option<usize> {
+        self.find_dead_state()
+    }
+
+    /// Find the terminal node, inserting it if the automata has none
+    ///
+    /// An inserted terminal node is unreachable, but it gives the IR
+    /// a block to jump to on failure
+    pub fn ensure_terminal_node(&mut self) -> usize {
+        if let Some(terminal) = self.try_find_terminal_node() {
+            return terminal
         }
 
-        panic!("Automata without a terminal state")
+        self.insert_state(State::Normal);
+        let terminal = self.states.len() - 1;
+        self.insert_edge(terminal, terminal, CharRange::FULL);
+
+        terminal
     }
 
     /// Return a list of node kinds of the automata states
     ///
     /// The indices of kinds in the list match the indices of
-    /// corresponding states in the automata
+    /// corresponding states in the automata. If the automata has no
+    /// terminal node, no state is `NodeKind::Terminal`
     pub fn node_kinds(&self) -> Vec<NodeKind> {
-        let terminal = self.find_terminal_node();
+        let terminal = self.try_find_terminal_node();
+        let terminal = terminal.unwrap_or(usize::MAX);
         let coedges = self.edges.iter().map(|(&(start, range), &end)| (end, range, start));
 
         (0..self.states.len()).map(|i| {
@@ -164,4 +303,314 @@ impl<T> Automata<T> {
         })
         .collect()
     }
+
+    /// Compute the coarsest partition of chars into equivalence classes
+    ///
+    /// Two chars are equivalent if the transitions from every state by them
+    /// lead to the same state, or both lead nowhere. Backends can map each
+    /// char to its class once, and look up the transition by the class
+    /// instead of scanning the ranges of the current state.
+    pub fn char_classes(&self) -> ClassMap {
+        // The intervals between the ends of all ranges are never split by
+        // a transition, so the chars of an interval are equivalent
+        let mut bounds = BTreeSet::new();
+        bounds.extend([0, 128, std::char::MAX as u32 + 1]);
+        for &(_, range) in self.edges.keys() {
+            bounds.insert(range.start as u32);
+            bounds.insert(range.end as u32 + 1);
+        }
+
+        // The surrogates are not chars, an interval may span over them
+        let bounds: Vec<_> = bounds.into_iter()
+            .filter(|&b| !(0xD801..0xE000).contains(&b))
+            .map(|b| if b == 0xD800 { 0xE000 } else { b })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let mut signatures = HashMap::new();
+        let mut representatives = vec![];
+        let mut intervals = vec![];
+        for w in bounds.windows(2) {
+            let start = std::char::from_u32(w[0]).unwrap();
+            let end = std::char::from_u32(w[1] - 1).unwrap_or('\u{D7FF}');
+
+            let signature: Vec<_> = (0..self.states.len())
+                .map(|st| self.transite(st, start))
+                .collect();
+            let count = signatures.len();
+            let class = *signatures.entry(signature).or_insert_with(|| {
+                representatives.push(start);
+                count
+            });
+
+            intervals.push((CharRange::new(start, end), class))
+        }
+
+        let mut ascii = [0; 128];
+        let mut ranges = vec![];
+        for (range, class) in merge_transitions(intervals) {
+            for ch in range.start as u32..(range.end as u32 + 1).min(128) {
+                ascii[ch as usize] = class
+            }
+
+            if !range.end.is_ascii() {
+                let start = if range.start.is_ascii() { '\u{80}' } else { range.start };
+                ranges.push((CharRange::new(start, range.end), class))
+            }
+        }
+
+        ClassMap { ascii, ranges, representatives }
+    }
+}
+
+/// A set of UTF-8 sequence suffixes with the states they lead to
+type Utf8Suffixes = Vec<(Vec<(u8, u8)>, usize)>;
+
+impl<T: Clone> Automata<T> {
+    /// Lower the automata to a byte-level automata over UTF-8 encoded input
+    ///
+    /// The edges of the resulting automata match bytes, where each byte `b`
+    /// is represented by `b as char`. Each char transition is replaced
+    /// by the paths of byte transitions produced by `Utf8Sequences`, so the
+    /// lowered automata accepts the UTF-8 encoding of the strings accepted by
+    /// the original automata.
+    ///
+    /// The states of the original automata keep their indices. The states in
+    /// the middle of UTF-8 sequences are normal states appended after them.
+    /// Bytes that cannot continue a valid UTF-8 sequence lead to the dead state.
+    pub fn to_utf8(&self) -> Automata<T> {
+        let mut automata = Automata {
+            states: self.states.clone(),
+            edges: BTreeMap::new(),
+        };
+
+        let dead = match self.find_dead_state() {
+            Some(dead) => dead,
+            None => {
+                automata.insert_state(State::Normal);
+                automata.states.len() - 1
+            },
+        };
+        automata.insert_edge(dead, dead, CharRange::FULL);
+
+        let mut queue = VecDeque::new();
+        for st in 0..self.states.len() {
+            if st == dead { continue }
+
+            let suffixes: Utf8Suffixes = self.transitions_from(st)
+                .filter(|&(_, to)| to != dead)
+                .flat_map(|(range, to)| {
+                    Utf8Sequences::new(range.start, range.end)
+                        .map(move |seq| {
+                            let seq = seq.as_slice().iter()
+                                .map(|r| (r.start, r.end))
+                                .collect();
+
+                            (seq, to)
+                        })
+                })
+                .collect();
+
+            queue.push_back((st, suffixes));
+        }
+
+        let mut stored = HashMap::<Utf8Suffixes, usize>::new();
+        while let Some((from, suffixes)) = queue.pop_front() {
+            // Split the first byte ranges of the suffixes into disjoint intervals
+            let mut bounds: Vec<u32> = suffixes.iter()
+                .flat_map(|(seq, _)| vec![seq[0].0 as u32, seq[0].1 as u32 + 1])
+                .collect();
+            bounds.sort_unstable();
+            bounds.dedup();
+
+            let mut edges = vec![];
+            for w in bounds.windows(2) {
+                let (start, end) = (w[0], w[1] - 1);
+
+                let mut tails: Utf8Suffixes = suffixes.iter()
+                    .filter(|(seq, _)| seq[0].0 as u32 <= start && end <= seq[0].1 as u32)
+                    .map(|(seq, to)| (seq[1..].to_vec(), *to))
+                    .collect();
+
+                if tails.is_empty() { continue }
+
+                // UTF-8 is prefix-free, so either all the tails are empty or none of them
+                let to =
+                    if tails[0].0.is_empty() { tails[0].1 }
+                    else {
+                        tails.sort();
+                        tails.dedup();
+
+                        if let Some(&i) = stored.get(&tails) { i }
+                        else {
+                            let i = automata.states.len();
+                            automata.insert_state(State::Normal);
+
+                            stored.insert(tails.clone(), i);
+                            queue.push_back((i, tails));
+
+                            i
+                        }
+                    };
+
+                let range = CharRange::new(start as u8 as char, end as u8 as char);
+                edges.push((range, to));
+            }
+
+            automata.insert_complete_edges(from, edges, dead)
+        }
+
+        automata
+    }
+}
+
+impl<T> Automata<T> {
+    /// Find a normal state such as the only transition from it is a full range loop
+    fn find_dead_state(&self) -> Option<usize> {
+        (0..self.states.len()).find(|&i| {
+            let mut transitions = self.transitions_from(i);
+
+            matches!(self.states[i], State::Normal)
+                && transitions.next() == Some((&CharRange::FULL, i))
+                && transitions.next().is_none()
+        })
+    }
+
+    /// Insert sorted disjoint transitions from the state `from`
+    ///
+    /// Adjacent ranges with the same destination are merged, and the gaps
+    /// between ranges are filled with transitions to the state `fallback`
+    fn insert_complete_edges(
+        &mut self,
+        from: usize,
+        edges: Vec<(CharRange, usize)>,
+        fallback: usize
+    ) {
+        let mut complete = vec![];
+
+        let mut next = 0u32;
+        for (range, to) in edges {
+            if (range.start as u32) > next {
+                let gap_end = std::char::from_u32(range.start as u32 - 1).unwrap();
+                complete.push((CharRange::new(std::char::from_u32(next).unwrap(), gap_end), fallback))
+            }
+
+            next = range.end as u32 + 1;
+            complete.push((range, to))
+        }
+
+        if let Some(start) = std::char::from_u32(next) {
+            complete.push((CharRange::new(start, std::char::MAX), fallback))
+        }
+
+        for (range, to) in merge_transitions(complete) {
+            self.insert_edge(from, to, range)
+        }
+    }
+}
+
+/// Merge adjacent sorted transitions with the same destination
+fn merge_transitions<I>(transitions: I) -> Vec<(CharRange, usize)>
+where I: IntoIterator<Item=(CharRange, usize)> {
+    let mut merged: Vec<(CharRange, usize)> = vec![];
+
+    for (range, to) in transitions {
+        match merged.last_mut() {
+            Some((last, last_to)) if *last_to == to => {
+                match last.concat(range) {
+                    Some(conc) => *last = conc,
+                    None => merged.push((range, to)),
+                }
+            },
+            _ => merged.push((range, to)),
+        }
+    }
+
+    merged
+}
+
+impl<T: Clone + PartialEq> Automata<T> {
+    /// Minimize the automata using Moore's algorithm
+    ///
+    /// Two states are equivalent if they have the same `State` value, and the
+    /// transitions from them by each char lead to equivalent states. The
+    /// minimized automata has one state per equivalence class, numbered in the
+    /// breadth-first order from the initial state, which keeps the index 0.
+    pub fn minimize(&self) -> Automata<T> {
+        // The initial partition separates states by their values
+        let mut values: Vec<&State<T>> = vec![];
+        let mut classes: Vec<usize> = self.states.iter()
+            .map(|st| {
+                if let Some(i) = values.iter().position(|&v| v == st) { i }
+                else {
+                    values.push(st);
+                    values.len() - 1
+                }
+            })
+            .collect();
+        let mut count = values.len();
+
+        // Refine the partition until it is stable
+        loop {
+            let mut signatures = HashMap::new();
+            let refined: Vec<usize> = (0..self.states.len())
+                .map(|st| {
+                    let signature = (classes[st], self.class_transitions(st, &classes));
+                    let len = signatures.len();
+
+                    *signatures.entry(signature).or_insert(len)
+                })
+                .collect();
+
+            classes = refined;
+
+            if signatures.len() == count { break }
+            count = signatures.len();
+        }
+
+        // Number the classes in the breadth-first order
+        let mut numbers: Vec<Option<usize>> = vec![None; count];
+        let mut representatives = vec![];
+        let mut queue = VecDeque::new();
+
+        numbers[classes[0]] = Some(0);
+        representatives.push(0);
+        queue.push_back(0);
+
+        while let Some(st) = queue.pop_front() {
+            for (_, to) in self.transitions_from(st) {
+                if numbers[classes[to]].is_none() {
+                    numbers[classes[to]] = Some(representatives.len());
+                    representatives.push(to);
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        let numbers: Vec<_> = classes.iter()
+            .map(|&c| numbers[c].unwrap_or(usize::MAX))
+            .collect();
+
+        let mut automata = Automata::new(self.states[0].clone());
+        for &st in &representatives[1..] {
+            automata.insert_state(self.states[st].clone())
+        }
+
+        for (from, &st) in representatives.iter().enumerate() {
+            for (range, to) in self.class_transitions(st, &numbers) {
+                automata.insert_edge(from, to, range)
+            }
+        }
+
+        automata
+    }
+
+    /// Transitions from the state with the destinations replaced by their classes
+    fn class_transitions(&self, state: usize, classes: &[usize]) -> Vec<(CharRange, usize)> {
+        let transitions = self.transitions_from(state)
+            .map(|(&range, to)| (range, classes[to]));
+
+        merge_transitions(transitions)
+    }
 }
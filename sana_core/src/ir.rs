@@ -2,6 +2,10 @@ use std::{ops::Not, collections::VecDeque};
 
 use crate::automata::{Automata, NodeKind, State};
 
+pub use optimize::Passes;
+
+mod optimize;
+
 /// An intermediate representation
 pub struct Ir<T> {
     pub blocks: Vec<Block<T>>
@@ -28,6 +32,14 @@ impl<T> Block<T> {
                 ops
         }
     }
+
+    fn ops_mut(&mut self) -> &mut Vec<Op<T>> {
+        match self {
+            Block::Block(ops)
+            | Block::Func(ops) =>
+                ops
+        }
+    }
 }
 
 /// IR opcodes
@@ -101,8 +113,10 @@ fn pprint_op<T: std::fmt::Debug>(op: &Op<T>) {
 
 impl<T: Clone> Ir<T> {
     /// Create IR from DFA
-    pub fn from_automata(automata: Automata<T>) -> Ir<T> {
-        let terminal = automata.find_terminal_node();
+    ///
+    /// If the DFA has no terminal node, an unreachable one is added
+    pub fn from_automata(mut automata: Automata<T>) -> Ir<T> {
+        let terminal = automata.ensure_terminal_node();
         let node_kinds = automata.node_kinds();
 
         let mut state_blocks: Vec<Option<usize>> =
@@ -298,21 +312,103 @@ pub enum VmResult<T> {
     Eoi,
 }
 
-#[derive(Debug, Clone)]
-pub struct Cursor<'input> {
-    pub input: &'input str,
+/// An input that can be scanned by a `Cursor`
+///
+/// A `str` is scanned by Unicode scalar values. A `[u8]` is scanned by bytes,
+/// and each byte `b` is represented by the char `b as char`, so the byte
+/// alphabet is the `'\0'..='\u{ff}'` range.
+pub trait Input {
+    /// Decode the character at the byte position `pos`
+    ///
+    /// Return the character together with its length in bytes
+    fn decode(&self, pos: usize) -> Option<(char, usize)>;
+
+    /// The part of the input between the byte positions `start` and `end`
+    fn slice(&self, start: usize, end: usize) -> &Self;
+
+    /// The length of the input in bytes
+    fn len(&self) -> usize;
+
+    /// The byte position `count` characters before the byte position `pos`
+    fn back(&self, pos: usize, count: usize) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Input for str {
+    fn decode(&self, pos: usize) -> Option<(char, usize)> {
+        let ch = self.get(pos..)?.chars().next()?;
+
+        Some((ch, ch.len_utf8()))
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &Self {
+        &self[start..end]
+    }
+
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn back(&self, pos: usize, count: usize) -> usize {
+        if count == 0 { return pos }
+
+        self[..pos].char_indices()
+            .rev()
+            .nth(count - 1)
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+impl Input for [u8] {
+    fn decode(&self, pos: usize) -> Option<(char, usize)> {
+        self.get(pos).map(|&b| (b as char, 1))
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &Self {
+        &self[start..end]
+    }
+
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn back(&self, pos: usize, count: usize) -> usize {
+        pos.saturating_sub(count)
+    }
+}
+
+#[derive(Debug)]
+pub struct Cursor<'input, I: ?Sized = str> {
+    pub input: &'input I,
     pub head: Option<char>,
-    iter: std::str::Chars<'input>,
+    width: usize,
     pos: usize,
+    furthest: usize,
+    eoi_seen: bool,
+}
+
+impl<'input, I: ?Sized> Clone for Cursor<'input, I> {
+    fn clone(&self) -> Self {
+        Cursor {
+            input: self.input,
+            head: self.head,
+            width: self.width,
+            pos: self.pos,
+            furthest: self.furthest,
+            eoi_seen: self.eoi_seen,
+        }
+    }
 }
 
-impl<'input> Cursor<'input> {
-    pub fn new(input: &'input str) -> Self {
-        let mut iter = input.chars();
-        let head = iter.next();
-        let pos = 0;
+impl<'input, I: Input + ?Sized> Cursor<'input, I> {
+    pub fn new(input: &'input I) -> Self {
+        let mut cursor = Cursor { input, head: None, width: 1, pos: 0, furthest: 0, eoi_seen: false };
+        cursor.rewind(0);
 
-        Cursor { input, iter, head, pos }
+        cursor
     }
 
     pub fn position(&self) -> usize {
@@ -320,32 +416,70 @@ impl<'input> Cursor<'input> {
     }
 
     pub fn shift(&mut self) {
-        self.pos += self.head
-            .map(char::len_utf8)
-            .unwrap_or(1);
-        self.head = self.iter.next();
+        self.pos += self.width;
+        self.decode_head();
     }
 
     /// Set the cursor position
     pub fn rewind(&mut self, pos: usize) {
-        self.iter = self.input[pos..].chars();
-        self.head = self.iter.next();
         self.pos = pos;
+        self.decode_head();
     }
 
     pub fn is_eoi(&self) -> bool {
         self.head.is_none()
     }
+
+    /// The furthest position the cursor has been at since it was created
+    ///
+    /// The char at this position is the last one a lexer has read, so its
+    /// matches depend only on the input up to this char
+    pub fn furthest(&self) -> usize {
+        self.furthest
+    }
+
+    /// Whether the cursor has been at the end of input since it was created
+    ///
+    /// If a lexer has seen the end of a partial input, its match may be
+    /// different on a longer input
+    pub fn eoi_seen(&self) -> bool {
+        self.eoi_seen
+    }
+
+    fn decode_head(&mut self) {
+        self.furthest = self.furthest.max(self.pos);
+
+        match self.input.decode(self.pos) {
+            Some((ch, width)) => {
+                self.head = Some(ch);
+                self.width = width;
+            },
+            None => {
+                self.head = None;
+                self.width = 1;
+                self.eoi_seen = true;
+            },
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Vm<'code, 'input, T> {
-    pub cursor: Cursor<'input>,
+#[derive(Debug)]
+pub struct Vm<'code, 'input, T, I: ?Sized = str> {
+    pub cursor: Cursor<'input, I>,
     code: &'code [Op<T>],
 }
 
-impl<'code, 'input, T: Clone> Vm<'code, 'input, T> {
-    pub fn new(code: &'code [Op<T>], input: &'input str) -> Self {
+impl<'code, 'input, T, I: ?Sized> Clone for Vm<'code, 'input, T, I> {
+    fn clone(&self) -> Self {
+        Vm {
+            cursor: self.cursor.clone(),
+            code: self.code,
+        }
+    }
+}
+
+impl<'code, 'input, T: Clone, I: Input + ?Sized> Vm<'code, 'input, T, I> {
+    pub fn new(code: &'code [Op<T>], input: &'input I) -> Self {
         let cursor = Cursor::new(input);
 
         Vm { cursor, code }
@@ -353,88 +487,145 @@ impl<'code, 'input, T: Clone> Vm<'code, 'input, T> {
 
     /// Execute the loaded code
     pub fn run(&mut self) -> VmResult<T> {
-        let mut inst_ptr = 0;
-        let mut jump_ptr = 0;
+        execute(self.code, &mut self.cursor)
+    }
+}
 
-        let mut action = None;
-        let start = self.cursor.position();
-        let mut end = start;
+/// Execute the code on the input of the given cursor
+///
+/// This is the same as `Vm::run`, but does not require a `Vm`
+pub fn execute<T, I>(code: &[Op<T>], cursor: &mut Cursor<'_, I>) -> VmResult<T>
+where T: Clone, I: Input + ?Sized {
+    let mut inst_ptr = 0;
+    let mut jump_ptr = 0;
 
-        if self.cursor.is_eoi() {
-            return VmResult::Eoi
-        }
+    let mut action = None;
+    let start = cursor.position();
+    let mut end = start;
 
-        loop {
-            match &self.code[inst_ptr] {
-                Op::Shift => {
-                    self.cursor.shift();
-                },
-                Op::JumpMatches { from, to, on_success } => {
-                    let cursor =
-                        if let Some(ch) = self.cursor.head { ch }
-                        else { break };
+    if cursor.is_eoi() {
+        return VmResult::Eoi
+    }
 
-                    if (*from..=*to).contains(&cursor) {
-                        inst_ptr = *on_success;
-                        jump_ptr = *on_success;
+    loop {
+        match &code[inst_ptr] {
+            Op::Shift => {
+                cursor.shift();
+            },
+            Op::JumpMatches { from, to, on_success } => {
+                let ch =
+                    if let Some(ch) = cursor.head { ch }
+                    else { break };
 
-                        continue;
-                    }
+                if (*from..=*to).contains(&ch) {
+                    inst_ptr = *on_success;
+                    jump_ptr = *on_success;
 
-                },
-                Op::JumpNotMatches { from, to, on_failure } => {
-                    let cursor =
-                        if let Some(ch) = self.cursor.head { ch }
-                        else { break };
+                    continue;
+                }
 
-                    if (*from..=*to).contains(&cursor).not() {
-                        inst_ptr = *on_failure;
-                        jump_ptr = *on_failure;
+            },
+            Op::JumpNotMatches { from, to, on_failure } => {
+                let ch =
+                    if let Some(ch) = cursor.head { ch }
+                    else { break };
 
-                        continue;
-                    }
-                },
-                Op::LoopMatches { from, to} => {
-                    let cursor =
-                        if let Some(ch) = self.cursor.head { ch }
-                        else { break };
+                if (*from..=*to).contains(&ch).not() {
+                    inst_ptr = *on_failure;
+                    jump_ptr = *on_failure;
 
-                    if (*from..=*to).contains(&cursor) {
-                        inst_ptr = jump_ptr;
+                    continue;
+                }
+            },
+            Op::LoopMatches { from, to} => {
+                let ch =
+                    if let Some(ch) = cursor.head { ch }
+                    else { break };
 
-                        continue
-                    }
-                },
-                Op::Jump(loc) => {
-                    inst_ptr = *loc;
-                    jump_ptr = *loc;
+                if (*from..=*to).contains(&ch) {
+                    inst_ptr = jump_ptr;
 
                     continue
-                },
-                Op::Set(act) => {
-                    action = Some(act.clone());
-                    end = self.cursor.position();
-                },
-                Op::Halt => break,
-            };
+                }
+            },
+            Op::Jump(loc) => {
+                inst_ptr = *loc;
+                jump_ptr = *loc;
 
-            inst_ptr += 1;
-        }
+                continue
+            },
+            Op::Set(act) => {
+                action = Some(act.clone());
+                end = cursor.position();
+            },
+            Op::Halt => break,
+        };
 
-        if action.is_none() && self.cursor.is_eoi().not() {
-            return VmResult::Error {
-                start,
-                end: self.cursor.position(),
-            }
+        inst_ptr += 1;
+    }
+
+    if action.is_none() && cursor.is_eoi().not() {
+        return VmResult::Error {
+            start,
+            end: cursor.position(),
         }
+    }
 
-        if end != self.cursor.position() { self.cursor.rewind(end) }
+    if end != cursor.position() { cursor.rewind(end) }
 
-        match action {
-            Some(action) =>
-                VmResult::Action { start, end, action },
-            None =>
-                VmResult::Eoi
-        }
+    match action {
+        Some(action) =>
+            VmResult::Action { start, end, action },
+        None =>
+            VmResult::Eoi
     }
 }
+
+/// Exclude the trailing context from the match of an action
+///
+/// `trailing` returns the length of the trailing context of an action in
+/// chars, see `Regex::fixed_length`. The cursor is moved back to the new end
+/// of the match.
+pub fn cut_trailing<T, I, F>(res: VmResult<T>, cursor: &mut Cursor<'_, I>, trailing: F) -> VmResult<T>
+where I: Input + ?Sized, F: Fn(&T) -> usize {
+    match res {
+        VmResult::Action { start, end, action } => {
+            let end = cursor.input.back(end, trailing(&action));
+            if end != cursor.position() { cursor.rewind(end) }
+
+            VmResult::Action { start, end, action }
+        },
+        res => res,
+    }
+}
+
+/// Run a byte-level lexer on the UTF-8 encoding of a string
+///
+/// Byte-level lexers are generated from automata lowered by `Automata::to_utf8`.
+/// Such automata accept only complete UTF-8 sequences, so only the end of an
+/// error may fall inside a char. It is moved back to the char boundary, which
+/// gives the same result as the char-level lexer.
+pub fn lex_utf8<'input, T, F>(cursor: &mut Cursor<'input, str>, lex: F) -> VmResult<T>
+where F: FnOnce(&mut Cursor<'input, [u8]>) -> VmResult<T> {
+    let input = cursor.input;
+    let floor = |pos: usize| {
+        (0..=pos).rev()
+            .find(|&i| input.is_char_boundary(i))
+            .unwrap_or(0)
+    };
+
+    let mut bytes = Cursor::new(input.as_bytes());
+    bytes.rewind(cursor.position());
+
+    let res = match lex(&mut bytes) {
+        VmResult::Error { start, end } =>
+            VmResult::Error { start, end: floor(end) },
+        res => res,
+    };
+
+    cursor.rewind(floor(bytes.position()));
+    cursor.furthest = cursor.furthest.max(bytes.furthest);
+    cursor.eoi_seen |= bytes.eoi_seen;
+
+    res
+}
@@ -0,0 +1,350 @@
+//! Optimization passes over the IR
+//!
+//! `Ir::from_automata` lowers each state of the DFA into a block as is. The
+//! passes clean up the result without changing the matches, so both the VM
+//! and the Rust code generated from the IR do less work.
+
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use super::{Block, Ir, Op};
+
+/// The optimization passes run by `Ir::optimize`
+///
+/// All passes are enabled by default. Each of them can be disabled to debug
+/// the IR or the code generated from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Passes {
+    /// Follow the jumps to blocks that only jump further, and replace the
+    /// jumps to blocks that only halt with `halt`
+    pub thread_jumps: bool,
+    /// Merge the blocks with the same code
+    pub dedup_blocks: bool,
+    /// Merge the adjacent ranges of the jumps to the same block
+    pub coalesce_ranges: bool,
+    /// Remove the blocks unreachable from the initial block
+    pub remove_dead_blocks: bool,
+}
+
+impl Passes {
+    /// All passes
+    pub const ALL: Passes = Passes {
+        thread_jumps: true,
+        dedup_blocks: true,
+        coalesce_ranges: true,
+        remove_dead_blocks: true,
+    };
+
+    /// No passes, the IR is left as is
+    pub const NONE: Passes = Passes {
+        thread_jumps: false,
+        dedup_blocks: false,
+        coalesce_ranges: false,
+        remove_dead_blocks: false,
+    };
+}
+
+impl Default for Passes {
+    fn default() -> Self {
+        Passes::ALL
+    }
+}
+
+impl<T: Clone + PartialEq> Ir<T> {
+    /// Optimize the IR with the given passes
+    ///
+    /// The passes are repeated while they change the IR, since threaded jumps
+    /// may make blocks equal, and merged blocks may make adjacent ranges jump
+    /// to the same block. The initial block keeps the index 0.
+    pub fn optimize(&mut self, passes: Passes) {
+        loop {
+            let mut changed = false;
+
+            if passes.thread_jumps { changed |= thread_jumps(self) }
+            if passes.dedup_blocks { changed |= dedup_blocks(self) }
+            if passes.coalesce_ranges { changed |= coalesce_ranges(self) }
+
+            if !changed { break }
+        }
+
+        if passes.remove_dead_blocks { remove_dead_blocks(self) }
+    }
+}
+
+/// The location of a jump, if the op jumps to another block
+fn jump_target<T>(op: &mut Op<T>) -> Option<&mut usize> {
+    match op {
+        Op::JumpMatches { on_success: loc, .. }
+        | Op::JumpNotMatches { on_failure: loc, .. }
+        | Op::Jump(loc) =>
+            Some(loc),
+        _ => None,
+    }
+}
+
+/// Turn the block into a function block
+///
+/// The code generators inline the code of normal blocks into every jump to
+/// them. A block that gets the jumps of a function block must be a function
+/// block too, or a loop through it would be inlined infinitely.
+fn make_func<T>(block: &mut Block<T>) {
+    if let Block::Block(ops) = block {
+        *block = Block::Func(mem::take(ops))
+    }
+}
+
+fn is_halt<T>(block: &Block<T>) -> bool {
+    matches!(block.ops(), [Op::Halt])
+}
+
+/// Thread the jumps through the blocks that only jump or halt
+///
+/// - A jump to a block with a single `jump` goes to its destination
+/// - `jump` to a block with a single `halt` is replaced with `halt`
+/// - `jnm a b H` followed by `jump X`, where `H` only halts, is replaced
+///   with `jm a b X` followed by `halt`
+/// - `jm a b H` among the match ops at the end of a block is removed, and
+///   the block halts after the rest of the match ops
+fn thread_jumps<T>(ir: &mut Ir<T>) -> bool {
+    let count = ir.blocks.len();
+    let halts: Vec<_> = ir.blocks.iter().map(is_halt).collect();
+    let targets: Vec<_> = (0..count)
+        .map(|block| {
+            let mut to = block;
+
+            // The chain is limited in case of a loop of jumps
+            for _ in 0..count {
+                match ir.blocks[to].ops() {
+                    [Op::Jump(next)] if *next != to => to = *next,
+                    _ => break,
+                }
+            }
+
+            to
+        })
+        .collect();
+
+    let mut changed = false;
+    let mut funcs = vec![];
+    for block in &mut ir.blocks {
+        for op in block.ops_mut() {
+            if let Some(loc) = jump_target(op) {
+                if targets[*loc] != *loc {
+                    funcs.push((*loc, targets[*loc]));
+                    *loc = targets[*loc];
+                    changed = true;
+                }
+            }
+        }
+
+        let ops = mem::take(block.ops_mut());
+        let mut threaded = Vec::with_capacity(ops.len());
+        let mut iter = ops.into_iter().peekable();
+        while let Some(op) = iter.next() {
+            match op {
+                Op::Jump(loc) if halts[loc] => {
+                    threaded.push(Op::Halt);
+                    changed = true;
+                },
+                Op::JumpNotMatches { from, to, on_failure } if halts[on_failure] => {
+                    match iter.peek() {
+                        Some(&Op::Jump(on_success)) => {
+                            iter.next();
+                            threaded.push(Op::JumpMatches { from, to, on_success });
+                            threaded.push(Op::Halt);
+                            changed = true;
+                        },
+                        _ => threaded.push(Op::JumpNotMatches { from, to, on_failure }),
+                    }
+                },
+                op => threaded.push(op),
+            }
+        }
+        *block.ops_mut() = threaded;
+
+        changed |= remove_halting_matches(block.ops_mut(), &halts);
+    }
+
+    for (from, to) in funcs {
+        if let Block::Func(_) = ir.blocks[from] {
+            make_func(&mut ir.blocks[to])
+        }
+    }
+
+    changed
+}
+
+/// Remove the match ops jumping to blocks that only halt
+///
+/// Only the match ops followed by other match ops up to a `halt` or the end
+/// of the block are removed. The ranges of such ops are disjoint, so the
+/// chars of the removed ones fall through to the `halt`.
+fn remove_halting_matches<T>(ops: &mut Vec<Op<T>>, halts: &[bool]) -> bool {
+    let is_match = |op: &Op<T>| matches!(op, Op::JumpMatches { .. } | Op::LoopMatches { .. });
+    let halting = |op: &Op<T>| matches!(op, Op::JumpMatches { on_success, .. } if halts[*on_success]);
+
+    // The match ops at the end of the block, before an optional `halt`
+    let end =
+        if let Some(Op::Halt) = ops.last() { ops.len() - 1 }
+        else { ops.len() };
+    let start = ops[..end].iter()
+        .rposition(|op| !is_match(op))
+        .map_or(0, |i| i + 1);
+
+    if !ops[start..end].iter().any(halting) { return false }
+
+    let mut i = 0;
+    ops.retain(|op| {
+        let keep = !((start..end).contains(&i) && halting(op));
+        i += 1;
+
+        keep
+    });
+
+    if !matches!(ops.last(), Some(Op::Halt)) {
+        ops.push(Op::Halt)
+    }
+
+    true
+}
+
+/// Merge the blocks with the same code into the first of them
+fn dedup_blocks<T: PartialEq>(ir: &mut Ir<T>) -> bool {
+    let mut by_len = HashMap::<usize, Vec<usize>>::new();
+    let mut repr: Vec<usize> = (0..ir.blocks.len()).collect();
+
+    for (i, block) in ir.blocks.iter().enumerate() {
+        let same_len = by_len.entry(block.ops().len()).or_default();
+
+        match same_len.iter().find(|&&j| ir.blocks[j].ops() == block.ops()) {
+            Some(&j) => repr[i] = j,
+            None => same_len.push(i),
+        }
+    }
+
+    let mut changed = false;
+    let mut funcs = vec![];
+    for block in &mut ir.blocks {
+        for op in block.ops_mut() {
+            if let Some(loc) = jump_target(op) {
+                if repr[*loc] != *loc {
+                    funcs.push((*loc, repr[*loc]));
+                    *loc = repr[*loc];
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for (from, to) in funcs {
+        if let Block::Func(_) = ir.blocks[from] {
+            make_func(&mut ir.blocks[to])
+        }
+    }
+
+    changed
+}
+
+/// Whether the range `b` starts right after the range `a`
+fn adjacent(a_end: char, b_start: char) -> bool {
+    a_end as u32 + 1 == b_start as u32
+        || (a_end == '\u{D7FF}' && b_start == '\u{E000}')
+}
+
+/// Merge the adjacent ranges of the match ops with the same destination
+///
+/// The ranges of a sequence of match ops are disjoint, so their order does
+/// not matter. A sequence is rewritten with the loops first, then the jumps
+/// sorted by their ranges.
+fn coalesce_ranges<T>(ir: &mut Ir<T>) -> bool {
+    let mut changed = false;
+
+    for block in &mut ir.blocks {
+        let ops = mem::take(block.ops_mut());
+        let mut coalesced = Vec::with_capacity(ops.len());
+        let mut run: Vec<(Option<usize>, char, char)> = vec![];
+
+        let mut flush = |run: &mut Vec<(Option<usize>, char, char)>, coalesced: &mut Vec<Op<T>>| {
+            let len = run.len();
+            run.sort_by_key(|&(to, from, _)| (to.is_some(), from));
+
+            let mut merged: Vec<(Option<usize>, char, char)> = vec![];
+            for &(to, from, end) in run.iter() {
+                match merged.iter_mut().find(|m| m.0 == to && adjacent(m.2, from)) {
+                    Some(m) => m.2 = end,
+                    None => merged.push((to, from, end)),
+                }
+            }
+            changed |= merged.len() < len;
+
+            coalesced.extend(merged.into_iter().map(|(on_success, from, to)| match on_success {
+                Some(on_success) => Op::JumpMatches { from, to, on_success },
+                None => Op::LoopMatches { from, to },
+            }));
+            run.clear();
+        };
+
+        for op in ops {
+            match op {
+                Op::JumpMatches { from, to, on_success } =>
+                    run.push((Some(on_success), from, to)),
+                Op::LoopMatches { from, to } =>
+                    run.push((None, from, to)),
+                op => {
+                    flush(&mut run, &mut coalesced);
+                    coalesced.push(op);
+                },
+            }
+        }
+        flush(&mut run, &mut coalesced);
+
+        *block.ops_mut() = coalesced;
+    }
+
+    changed
+}
+
+/// Remove the blocks unreachable from the initial block
+///
+/// The remaining blocks keep their order, and the jumps are renumbered
+fn remove_dead_blocks<T>(ir: &mut Ir<T>) {
+    let mut reachable = vec![false; ir.blocks.len()];
+    let mut queue = VecDeque::new();
+    reachable[0] = true;
+    queue.push_back(0);
+
+    while let Some(block) = queue.pop_front() {
+        for op in ir.blocks[block].ops_mut() {
+            if let Some(&mut loc) = jump_target(op) {
+                if !reachable[loc] {
+                    reachable[loc] = true;
+                    queue.push_back(loc);
+                }
+            }
+        }
+    }
+
+    let mut renumber = vec![usize::MAX; ir.blocks.len()];
+    let mut count = 0;
+    for (i, &live) in reachable.iter().enumerate() {
+        if live {
+            renumber[i] = count;
+            count += 1;
+        }
+    }
+
+    let blocks = mem::take(&mut ir.blocks);
+    ir.blocks = blocks.into_iter()
+        .zip(reachable)
+        .filter(|(_, live)| *live)
+        .map(|(mut block, _)| {
+            for op in block.ops_mut() {
+                if let Some(loc) = jump_target(op) {
+                    *loc = renumber[*loc];
+                }
+            }
+
+            block
+        })
+        .collect();
+}
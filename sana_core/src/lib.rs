@@ -8,9 +8,9 @@
 //!
 //! If you just want generate a lexer, use the main crate (`sana`) instead.
 
-use regex::{Regex, Derivative, RegexVector};
+use regex::{Regex, Derivative, ParseError, RegexVector};
 use automata::{State, Automata};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 pub mod regex;
 pub mod automata;
@@ -23,8 +23,32 @@ pub mod dot;
 pub enum Error {
     /// Ambiguity error for rules with given indices
     ///
-    /// The rules can match the same string but have the same precedence
-    AmbiguityError(usize, usize)
+    /// The rules can match the same string but have the same precedence.
+    /// The string is the shortest one matched by both rules
+    AmbiguityError(usize, usize, String)
+}
+
+/// A rule that is never chosen in any state of the DFA
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadowed {
+    /// The index of the shadowed rule
+    pub rule: usize,
+    /// The index of a rule chosen instead of the shadowed one and the shortest
+    /// string they both match
+    ///
+    /// It is `None` if the rule does not match any string
+    pub by: Option<(usize, String)>,
+}
+
+/// The result of the rule set analysis
+#[derive(Debug, Clone)]
+pub struct Analysis<T> {
+    /// The DFA of the rule set
+    pub automata: Automata<T>,
+    /// The rules that are never chosen
+    pub shadowed: Vec<Shadowed>,
+    /// The ambiguity errors for all pairs of conflicting rules
+    pub conflicts: Vec<Error>,
 }
 
 /// A lexer rule
@@ -37,6 +61,26 @@ pub struct Rule<T> {
     pub action: T,
 }
 
+impl<T> Rule<T> {
+    /// Create a rule
+    pub fn new(regex: Regex, priority: usize, action: T) -> Self {
+        Rule { regex, priority, action }
+    }
+
+    /// Add a trailing context to the rule, as in the `r1/r2` rules of flex
+    ///
+    /// The rule matches only if the match is followed by a string matched by
+    /// the trailing context. The DFA matches both, so the lexer must exclude
+    /// the trailing context from the match, for example with
+    /// `ir::cut_trailing` and the length returned by `Regex::fixed_length`.
+    pub fn followed_by(mut self, trailing: Regex) -> Self {
+        self.regex = Regex::Concat(vec![self.regex, trailing]);
+        self.regex.normalize();
+
+        self
+    }
+}
+
 impl<T: Clone> Rule<T> {
     /// Construct DFA using regular expression derivatives
     pub fn construct_dfa(&self) -> Automata<T> {
@@ -89,30 +133,63 @@ pub struct RuleSet<T> {
     pub rules: Vec<Rule<T>>
 }
 
+impl<T> Default for RuleSet<T> {
+    fn default() -> Self {
+        RuleSet { rules: vec![] }
+    }
+}
+
+impl<T> RuleSet<T> {
+    /// Create an empty rule set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule with a regular expression in the extended syntax
+    ///
+    /// See `Regex::parse` for the syntax
+    pub fn push_regex(&mut self, regex: &str, priority: usize, action: T) -> Result<&mut Self, ParseError> {
+        let regex = Regex::parse(regex)?;
+        self.rules.push(Rule { regex, priority, action });
+
+        Ok(self)
+    }
+
+    /// Add a rule that matches the given string
+    pub fn push_token(&mut self, token: &str, priority: usize, action: T) -> &mut Self {
+        let regex = Regex::literal_str(token);
+        self.rules.push(Rule { regex, priority, action });
+
+        self
+    }
+}
+
 impl<T: Clone> RuleSet<T> {
     /// From all rules with index i ∈ rule_indices, return the rule
     /// with the higherst priority
     ///
-    /// If there are more than one such rules, return the ambiguity error
-    fn top_rule<I>(&self, mut rule_indices: I) -> Result<Option<&Rule<T>>, Error>
+    /// If there are more than one such rules, return the first one and
+    /// all pairs of them
+    fn top_rule<I>(&self, rule_indices: I) -> (Option<usize>, Vec<(usize, usize)>)
     where I: Iterator<Item=usize> {
-        let ix =
-            if let Some(ix) = rule_indices.next() { ix }
-            else { return Ok(None) };
-
-        let (mut top_ix, mut top_prio) = (ix, self.rules[ix].priority);
+        let mut top: Vec<usize> = vec![];
         for i in rule_indices {
             use std::cmp::Ordering::*;
 
             let prio = self.rules[i].priority;
-            match prio.cmp(&top_prio) {
-                Less => (),
-                Equal => return Err(Error::AmbiguityError(top_ix, i)),
-                Greater => { top_ix = i; top_prio = prio }
+            match top.first().map(|&ix| prio.cmp(&self.rules[ix].priority)) {
+                Some(Less) => (),
+                Some(Equal) => top.push(i),
+                Some(Greater) | None => top = vec![i],
             }
         }
 
-        Ok(Some(&self.rules[top_ix]))
+        let mut pairs = vec![];
+        for (k, &ix) in top.iter().enumerate() {
+            pairs.extend(top[k + 1..].iter().map(|&i| (ix, i)))
+        }
+
+        (top.first().copied(), pairs)
     }
 
     /// Construct a DFA from a rule set
@@ -123,17 +200,91 @@ impl<T: Clone> RuleSet<T> {
     /// If there's more than one rule with the same priority that matches
     /// the same input, then an ambiguity error is returned
     pub fn construct_dfa(&self) -> Result<Automata<T>, Error> {
+        let mut analysis = self.analyze();
+
+        if analysis.conflicts.is_empty() { Ok(analysis.automata) }
+        else { Err(analysis.conflicts.swap_remove(0)) }
+    }
+
+    /// Construct a DFA from a rule set, find the shadowed rules and
+    /// collect all conflicts between the rules
+    ///
+    /// A rule is shadowed if it is not chosen in any state of the DFA, so the
+    /// lexer never produces its action. Each pair of conflicting rules is
+    /// reported once. When the rules conflict, the first of them is chosen.
+    ///
+    /// Since the states are visited in the breadth-first order, the example
+    /// strings are the shortest ones.
+    pub fn analyze(&self) -> Analysis<T> {
+        let mut wins = vec![false; self.rules.len()];
+        let mut by = vec![None; self.rules.len()];
+        let mut conflicts = vec![];
+        let mut known_pairs = HashSet::new();
+        let visit = |vec: &RegexVector, witness: &str| {
+            let nullables: Vec<_> = vec.nullables().collect();
+            let (top, pairs) = self.top_rule(nullables.iter().copied());
+
+            for (a, b) in pairs {
+                if known_pairs.insert((a, b)) {
+                    conflicts.push(Error::AmbiguityError(a, b, witness.into()))
+                }
+            }
+
+            if let Some(top) = top {
+                wins[top] = true;
+
+                for &i in &nullables {
+                    if i != top && by[i].is_none() {
+                        by[i] = Some((top, witness.to_string()))
+                    }
+                }
+            }
+
+            match top {
+                Some(ix) => State::Action(self.rules[ix].action.clone()),
+                _ => State::Normal,
+            }
+        };
+
+        let automata = self.explore(visit);
+
+        let shadowed = by.into_iter()
+            .enumerate()
+            .filter(|&(i, _)| !wins[i])
+            .map(|(rule, by)| Shadowed { rule, by })
+            .collect();
+
+        Analysis { automata, shadowed, conflicts }
+    }
+
+    /// Construct a DFA, in which each action state holds the actions of all
+    /// rules that match in this state
+    ///
+    /// The actions are sorted from the highest priority to the lowest one,
+    /// so the first action is the one chosen by `construct_dfa`
+    pub fn construct_tagged_dfa(&self) -> Automata<Vec<T>> {
+        self.explore(|vec, _| {
+            let mut nullables: Vec<_> = vec.nullables().collect();
+            if nullables.is_empty() { return State::Normal }
+
+            nullables.sort_by_key(|&i| std::cmp::Reverse(self.rules[i].priority));
+
+            State::Action(nullables.into_iter().map(|i| self.rules[i].action.clone()).collect())
+        })
+    }
+
+    /// Walk the DFA of the rule set in the breadth-first order
+    ///
+    /// `visit` computes the state of each regex vector, given the shortest
+    /// string that leads to it
+    fn explore<S, F>(&self, mut visit: F) -> Automata<S>
+    where F: FnMut(&RegexVector, &str) -> State<S> {
         let vector = RegexVector {
             exprs: self.rules.iter().map(|r| r.regex.clone()).collect()
         };
-        let rule = self.top_rule(vector.nullables())?;
-        let state =
-            match rule {
-                Some(rule) => State::Action(rule.action.clone()),
-                _ => State::Normal,
-            };
 
-        let mut automata = Automata::new(state);
+        let mut automata = Automata::new(visit(&vector, ""));
+        let mut witnesses = vec![String::new()];
         let mut queue = VecDeque::new();
         let mut stored = HashMap::<_, usize>::new();
 
@@ -152,11 +303,11 @@ impl<T: Clone> RuleSet<T> {
                     if let Some(&i) = stored.get(&dvec) { i }
                     else {
                         let i = stored.len();
-                        let state =
-                            match self.top_rule(dvec.nullables())? {
-                                Some(rule) => State::Action(rule.action.clone()),
-                                _ => State::Normal,
-                            };
+                        let mut witness = witnesses[from].clone();
+                        witness.push(class.pick());
+
+                        let state = visit(&dvec, &witness);
+                        witnesses.push(witness);
 
                         queue.push_back(dvec.clone());
                         stored.insert(dvec, i);
@@ -192,6 +343,6 @@ impl<T: Clone> RuleSet<T> {
             }
         }
 
-        Ok(automata)
+        automata
     }
 }
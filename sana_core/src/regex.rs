@@ -8,6 +8,10 @@ use std::{
 use std::ops::Not;
 use crate::automata::CharRange;
 
+pub use parse::ParseError;
+
+mod parse;
+
 // Hashing is used for regular expression normalization
 fn hash<T: Hash>(t: &T) -> u64 {
     let mut s = fnv::FnvHasher::default();
@@ -37,6 +41,15 @@ impl Class {
         class
     }
 
+    /// Create a class of bytes, where each byte `b` is represented by `b as char`
+    fn from_bytes<I>(ranges: I) -> Self
+    where I: IntoIterator<Item=(u8, u8)> {
+        let ranges = ranges.into_iter()
+            .map(|(start, end)| hir::ClassUnicodeRange::new(start as char, end as char));
+
+        Class(hir::ClassUnicode::new(ranges))
+    }
+
     fn is_empty(&self) -> bool {
         self.0.ranges().is_empty()
     }
@@ -61,7 +74,7 @@ impl Class {
     }
 }
 
-#[allow(clippy::derive_hash_xor_eq)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 impl std::hash::Hash for Class {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
         for r in self.0.ranges() {
@@ -100,6 +113,14 @@ pub enum Regex {
     Concat(Vec<Regex>),
     /// Klenee star
     Loop(Box<Regex>),
+    /// Counted repetition `r{min,max}`
+    ///
+    /// `max` is `None` for repetitions without an upper bound
+    Repeat {
+        regex: Box<Regex>,
+        min: u32,
+        max: Option<u32>,
+    },
     /// Logical or (alteration)
     Or(Vec<Regex>),
     /// Logical and (intersection)
@@ -115,9 +136,7 @@ pub fn pprint_regex(regex: &Regex) {
 }
 
 fn print_regex_indent(regex: &Regex, indent: usize) {
-    let ws: String = std::iter::repeat(' ')
-        .take(indent)
-        .collect();
+    let ws = " ".repeat(indent);
 
     match regex {
         Regex::Nothing => println!("{}Nothing", ws),
@@ -136,6 +155,14 @@ fn print_regex_indent(regex: &Regex, indent: usize) {
 
             print_regex_indent(r, indent + 2)
         },
+        Regex::Repeat { regex, min, max } => {
+            match max {
+                Some(max) => println!("{}Repeat {{{},{}}}", ws, min, max),
+                None => println!("{}Repeat {{{},}}", ws, min),
+            }
+
+            print_regex_indent(regex, indent + 2)
+        },
         Regex::Or(list) => {
             println!("{}Or", ws);
 
@@ -224,6 +251,51 @@ impl Regex {
         Regex::Concat(string.chars().map(Regex::Literal).collect())
     }
 
+    /// Create a byte regular expression that matches the given byte string
+    pub fn literal_bytes(bytes: &[u8]) -> Regex {
+        if bytes.is_empty() { return Regex::Empty }
+
+        Regex::Concat(bytes.iter().map(|&b| Regex::Literal(b as char)).collect())
+    }
+
+    /// The length in chars of the strings matched by the regular expression
+    ///
+    /// Return `None` if it matches strings of different lengths or does not
+    /// match anything. It is used to exclude a trailing context of a fixed
+    /// length from the match, see `Rule::followed_by`
+    pub fn fixed_length(&self) -> Option<usize> {
+        crate::Rule::new(self.clone(), 0, ()).construct_dfa().fixed_length()
+    }
+
+    /// Convert a regular expression to a byte regular expression
+    ///
+    /// A byte regular expression matches a sequence of bytes, where each
+    /// byte `b` is represented by the char `b as char`. Unicode literals and
+    /// classes match their UTF-8 encoding. Byte literals and byte classes,
+    /// such as `(?-u:\xFF)`, match themselves.
+    ///
+    /// To parse byte classes that match invalid UTF-8, `regex_syntax` must be
+    /// configured with `allow_invalid_utf8(true)`.
+    pub fn try_from_bytes(hir: hir::Hir) -> Result<Regex, &'static str> {
+        from_hir(hir, true)
+    }
+
+    /// Parse a regular expression in the extended syntax
+    ///
+    /// The syntax is the same as the syntax of the `#[regex]` attribute,
+    /// for example `"[a-z]+" & !("if" | "in")`. See the `parse` module
+    /// docs for the details.
+    pub fn parse(source: &str) -> Result<Regex, ParseError> {
+        parse::Parser::new(source, false).parse()
+    }
+
+    /// Parse a byte regular expression in the extended syntax
+    ///
+    /// The literals are converted as in `Regex::try_from_bytes`
+    pub fn parse_bytes(source: &str) -> Result<Regex, ParseError> {
+        parse::Parser::new(source, true).parse()
+    }
+
     /// Normalize the regular expression
     ///
     /// The purpose of normalization is to make equivalent expressions equal. This
@@ -245,7 +317,7 @@ impl Regex {
 
                 flatten_concat(list);
 
-                if list.iter().any(|e| *e == Regex::Nothing) {
+                if list.contains(&Regex::Nothing) {
                     return *self = Regex::Nothing
                 }
 
@@ -255,7 +327,7 @@ impl Regex {
                     return *self = list[0].clone()
                 }
                 if list.is_empty() {
-                    return *self = Regex::Empty
+                    *self = Regex::Empty
                 }
             },
             Regex::Loop(e) => {
@@ -271,6 +343,36 @@ impl Regex {
                     _ => (),
                 }
             },
+            Regex::Repeat { regex, min, max } => {
+                regex.normalize();
+
+                // A nullable expression can match any of its copies
+                // with the empty string, so the lower bound does not matter
+                if regex.is_nullable() { *min = 0 }
+
+                if let Some(max) = *max {
+                    if *min > max {
+                        return *self = Regex::Nothing
+                    }
+                    if max == 0 {
+                        return *self = Regex::Empty
+                    }
+                }
+
+                match (regex.as_ref(), *min, *max) {
+                    (Regex::Empty, _, _) =>
+                        *self = Regex::Empty,
+                    (Regex::Nothing, 0, _) =>
+                        *self = Regex::Empty,
+                    (Regex::Nothing, _, _) =>
+                        *self = Regex::Nothing,
+                    (_, 0, None) =>
+                        *self = Regex::Loop(regex.clone()),
+                    (_, 1, Some(1)) =>
+                        *self = *regex.clone(),
+                    _ => (),
+                }
+            },
             Regex::Or(list) => {
                 for e in list.as_mut_slice() {
                     e.normalize()
@@ -278,7 +380,7 @@ impl Regex {
 
                 flatten_or(list);
 
-                if list.iter().any(|e| *e == Regex::Anything) {
+                if list.contains(&Regex::Anything) {
                     return *self = Regex::Anything
                 }
 
@@ -291,7 +393,7 @@ impl Regex {
                     return *self = Regex::Nothing
                 }
 
-                list.sort_by(|l, r| hash(l).cmp(&hash(r)));
+                list.sort_by_key(hash);
                 list.dedup()
             },
             Regex::And(list) => {
@@ -301,7 +403,7 @@ impl Regex {
 
                 flatten_and(list);
 
-                if list.iter().any(|e| *e == Regex::Nothing) {
+                if list.contains(&Regex::Nothing) {
                     return *self = Regex::Nothing
                 }
 
@@ -314,7 +416,7 @@ impl Regex {
                     return *self = Regex::Anything
                 }
 
-                list.sort_by(|l, r| hash(l).cmp(&hash(r)));
+                list.sort_by_key(hash);
                 list.dedup()
             },
             Regex::Not(e) => {
@@ -346,6 +448,8 @@ impl Regex {
             Regex::Concat(list) =>
                 list.iter().all(|e| e.is_nullable()),
             Regex::Loop(_) => true,
+            Regex::Repeat { regex, min, .. } =>
+                *min == 0 || regex.is_nullable(),
             Regex::Or(list) =>
                 list.iter().any(|e| e.is_nullable()),
             Regex::And(list) =>
@@ -391,6 +495,26 @@ impl Derivative for Regex {
                 e.derivative(ch),
                 Regex::Loop(e.clone())
             ]),
+            // For a nullable `r`, `r{n,m}` is the same as `r{0,m}`, and
+            // `c⁻¹ r{0,m} = (c⁻¹ r) ⋅ r{0,m-1}`. Otherwise the first character
+            // must be matched by the first copy of `r`
+            Regex::Repeat { regex, min, max } => {
+                let min =
+                    if regex.is_nullable() { 0 }
+                    else { min.saturating_sub(1) };
+
+                match max {
+                    Some(0) => Regex::Nothing,
+                    _ => Regex::Concat(vec![
+                        regex.derivative(ch),
+                        Regex::Repeat {
+                            regex: regex.clone(),
+                            min,
+                            max: max.map(|m| m - 1),
+                        }
+                    ]),
+                }
+            },
             Regex::Or(list) => Regex::Or(
                 list.iter()
                     .map(|e| e.derivative(ch))
@@ -420,86 +544,132 @@ impl TryFrom<hir::Hir> for Regex {
     type Error = &'static str;
 
     fn try_from(hir: hir::Hir) -> Result<Regex, Self::Error> {
-        use hir::*;
+        from_hir(hir, false)
+    }
+}
 
-        match hir.into_kind() {
-            HirKind::Empty => {
-                Ok(Regex::Empty)
-            },
-            HirKind::Concat(concat) => {
-                let mut out = Vec::with_capacity(concat.len());
-
-                fn extend(mir: Regex, out: &mut Vec<Regex>) {
-                    match mir {
-                        Regex::Concat(nested) => {
-                            for child in nested {
-                                extend(child, out);
-                            }
-                        },
-                        mir => out.push(mir),
-                    }
-                }
+fn from_hir(hir: hir::Hir, bytes: bool) -> Result<Regex, &'static str> {
+    use hir::*;
 
-                for hir in concat {
-                    extend(Regex::try_from(hir)?, &mut out);
+    match hir.into_kind() {
+        HirKind::Empty => {
+            Ok(Regex::Empty)
+        },
+        HirKind::Concat(concat) => {
+            let mut out = Vec::with_capacity(concat.len());
+
+            fn extend(mir: Regex, out: &mut Vec<Regex>) {
+                match mir {
+                    Regex::Concat(nested) => {
+                        for child in nested {
+                            extend(child, out);
+                        }
+                    },
+                    mir => out.push(mir),
                 }
+            }
 
-                Ok(Regex::Concat(out))
-            },
-            HirKind::Alternation(alternation) => {
-                let alternation = alternation
-                    .into_iter()
-                    .map(Regex::try_from)
-                    .collect::<Result<_, _>>()?;
+            for hir in concat {
+                extend(from_hir(hir, bytes)?, &mut out);
+            }
 
-                Ok(Regex::Or(alternation))
-            },
-            HirKind::Literal(hir::Literal::Unicode(literal)) => {
-                Ok(Regex::Literal(literal))
-            },
-            HirKind::Literal(_) =>
-                Err("Only Unicode literals are supported"),
-            HirKind::Class(hir::Class::Unicode(class)) =>
-                Ok(Regex::Class(Class(class))),
-            HirKind::Class(_) =>
-                Err("Only Unicode classes are supported"),
-            HirKind::Repetition(repetition) => {
-                if repetition.greedy.not() {
-                    return Err("#[regex]: non-greedy parsing is currently unsupported.");
-                }
+            Ok(Regex::Concat(out))
+        },
+        HirKind::Alternation(alternation) => {
+            let alternation = alternation
+                .into_iter()
+                .map(|hir| from_hir(hir, bytes))
+                .collect::<Result<_, _>>()?;
 
-                let kind = repetition.kind;
-                let mir = Regex::try_from(*repetition.hir)?;
+            Ok(Regex::Or(alternation))
+        },
+        HirKind::Literal(hir::Literal::Unicode(literal)) if bytes => {
+            let mut buf = [0; 4];
+            let encoded = literal.encode_utf8(&mut buf);
 
-                match kind {
-                    RepetitionKind::ZeroOrOne => {
-                        Ok(Regex::Or(vec![mir, Regex::Empty]))
-                    },
-                    RepetitionKind::ZeroOrMore => {
-                        Ok(Regex::Loop(Box::new(mir)))
-                    },
-                    RepetitionKind::OneOrMore => {
-                        Ok(Regex::Concat(vec![
-                            mir.clone(),
-                            Regex::Loop(Box::new(mir)),
-                        ]))
-                    },
-                    RepetitionKind::Range(..) => {
-                        Err("#[regex]: {n,m} repetition range is currently unsupported.")
-                    },
-                }
-            },
-            HirKind::Group(group) => {
-                Regex::try_from(*group.hir)
-            },
-            HirKind::WordBoundary(_) => {
-                Err("#[regex]: word boundaries are currently unsupported.")
-            },
-            HirKind::Anchor(_) => {
-                Err("#[regex]: anchors in #[regex] are currently unsupported.")
-            },
+            Ok(Regex::literal_bytes(encoded.as_bytes()))
+        },
+        HirKind::Literal(hir::Literal::Unicode(literal)) => {
+            Ok(Regex::Literal(literal))
+        },
+        HirKind::Literal(hir::Literal::Byte(byte)) if bytes => {
+            Ok(Regex::Literal(byte as char))
+        },
+        HirKind::Literal(_) =>
+            Err("Only Unicode literals are supported"),
+        HirKind::Class(hir::Class::Unicode(class)) if bytes => {
+            Ok(utf8_class(&class))
+        },
+        HirKind::Class(hir::Class::Unicode(class)) =>
+            Ok(Regex::Class(Class(class))),
+        HirKind::Class(hir::Class::Bytes(class)) if bytes => {
+            let ranges = class.ranges().iter()
+                .map(|r| (r.start(), r.end()));
+
+            Ok(Regex::Class(crate::regex::Class::from_bytes(ranges)))
+        },
+        HirKind::Class(_) =>
+            Err("Only Unicode classes are supported"),
+        HirKind::Repetition(repetition) => {
+            if repetition.greedy.not() {
+                return Err("#[regex]: non-greedy parsing is currently unsupported.");
+            }
+
+            let kind = repetition.kind;
+            let mir = from_hir(*repetition.hir, bytes)?;
+
+            match kind {
+                RepetitionKind::ZeroOrOne => {
+                    Ok(Regex::Or(vec![mir, Regex::Empty]))
+                },
+                RepetitionKind::ZeroOrMore => {
+                    Ok(Regex::Loop(Box::new(mir)))
+                },
+                RepetitionKind::OneOrMore => {
+                    Ok(Regex::Concat(vec![
+                        mir.clone(),
+                        Regex::Loop(Box::new(mir)),
+                    ]))
+                },
+                RepetitionKind::Range(range) => {
+                    let (min, max) = match range {
+                        RepetitionRange::Exactly(n) => (n, Some(n)),
+                        RepetitionRange::AtLeast(n) => (n, None),
+                        RepetitionRange::Bounded(n, m) => (n, Some(m)),
+                    };
+
+                    Ok(Regex::Repeat { regex: Box::new(mir), min, max })
+                },
+            }
+        },
+        HirKind::Group(group) => {
+            from_hir(*group.hir, bytes)
+        },
+        HirKind::WordBoundary(_) => {
+            Err("#[regex]: word boundaries are currently unsupported.")
+        },
+        HirKind::Anchor(_) => {
+            Err("#[regex]: anchors in #[regex] are currently unsupported.")
+        },
+    }
+}
+
+/// Convert a Unicode class to a byte regular expression matching UTF-8
+/// encoded characters of the class
+fn utf8_class(class: &hir::ClassUnicode) -> Regex {
+    let mut alternation = vec![];
+
+    for range in class.ranges() {
+        for seq in utf8_ranges::Utf8Sequences::new(range.start(), range.end()) {
+            let concat = seq.as_slice().iter()
+                .map(|r| Regex::Class(Class::from_bytes(Some((r.start, r.end)))))
+                .collect();
+
+            alternation.push(Regex::Concat(concat))
         }
     }
+
+    Regex::Or(alternation)
 }
 
 fn collect_classes(classes: &mut Vec<Class>, regex: &Regex) {
@@ -522,6 +692,8 @@ fn collect_classes(classes: &mut Vec<Class>, regex: &Regex) {
         },
         Regex::Loop(e) =>
             collect_classes(classes, e),
+        Regex::Repeat { regex, .. } =>
+            collect_classes(classes, regex),
         Regex::Or(list) =>
             for e in list { collect_classes(classes, e) },
         Regex::And(list) =>
@@ -562,7 +734,7 @@ impl ClassSet {
             }
 
             if set.len() > 64 {
-                set.sort_by_key(|l| hash(l));
+                set.sort_by_key(hash);
                 set.dedup()
             }
         }
@@ -0,0 +1,203 @@
+//! A parser of the extended regular expression syntax
+//!
+//! ```text
+//! regex =
+//!     regex '|' regex
+//!     / regex '&' regex
+//!     / regex '.' regex
+//!     / '!' regex
+//!     / '(' regex ')'
+//!     / literal
+//! ```
+//!
+//! Here, `literal` is a double quoted regular expression in the
+//! [regex](https://docs.rs/regex) crate syntax. Inside of a literal, `\"`
+//! denotes the quote, and all other characters are passed to the regex
+//! parser as is. So `"\d+"` is a literal for `\d+`.
+
+use std::fmt;
+
+use super::Regex;
+
+/// An error of the extended regular expression parser
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The byte offset of the error in the source string
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub(super) struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+    bytes: bool,
+}
+
+impl<'a> Parser<'a> {
+    pub(super) fn new(source: &'a str, bytes: bool) -> Self {
+        Parser { source, pos: 0, bytes }
+    }
+
+    pub(super) fn parse(mut self) -> Result<Regex, ParseError> {
+        if self.peek().is_none() {
+            return Err(self.error("Empty regex"))
+        }
+
+        let regex = self.parse_or()?;
+
+        match self.peek() {
+            None => Ok(regex),
+            Some(')') => Err(self.error("Unmatched closing parenthesis")),
+            Some(_) => Err(self.error("Expected an operator")),
+        }
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { position: self.pos, message: message.into() }
+    }
+
+    /// Skip whitespace and return the next char
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.source[self.pos..];
+        let trimmed = rest.trim_start();
+        self.pos += rest.len() - trimmed.len();
+
+        trimmed.chars().next()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += ch.len_utf8();
+
+            true
+        }
+        else { false }
+    }
+
+    fn parse_infix<F>(
+        &mut self,
+        op: char,
+        cons: F,
+        higher: fn(&mut Self) -> Result<Regex, ParseError>
+    ) -> Result<Regex, ParseError>
+    where F: FnOnce(Vec<Regex>) -> Regex {
+        let head = higher(self)?;
+
+        let mut tail = vec![];
+        while self.eat(op) {
+            tail.push(higher(self)?)
+        }
+
+        if tail.is_empty() {
+            Ok(head)
+        }
+        else {
+            Ok(cons(Some(head).into_iter().chain(tail).collect()))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Regex, ParseError> {
+        self.parse_infix('|', Regex::Or, Self::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<Regex, ParseError> {
+        self.parse_infix('&', Regex::And, Self::parse_dot)
+    }
+
+    fn parse_dot(&mut self) -> Result<Regex, ParseError> {
+        self.parse_infix('.', Regex::Concat, Self::parse_not)
+    }
+
+    fn parse_not(&mut self) -> Result<Regex, ParseError> {
+        if self.eat('!') {
+            let inner = self.parse_not()?;
+
+            Ok(Regex::Not(Box::new(inner)))
+        }
+        else {
+            self.parse_other()
+        }
+    }
+
+    fn parse_other(&mut self) -> Result<Regex, ParseError> {
+        match self.peek() {
+            Some('"') => self.parse_literal(),
+            Some('(') => {
+                let open = self.pos;
+                self.pos += 1;
+
+                let regex = self.parse_or()?;
+                if !self.eat(')') {
+                    return Err(ParseError {
+                        position: open,
+                        message: "Unclosed parenthesis".into(),
+                    })
+                }
+
+                Ok(regex)
+            },
+            Some(_) => Err(self.error("Expected a literal or a parenthesis")),
+            None => Err(self.error("Unexpected end of regex")),
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Regex, ParseError> {
+        let start = self.pos;
+        let mut pattern = String::new();
+        // The pattern offsets of the escaped quotes
+        let mut escapes = vec![];
+        let mut chars = self.source[start + 1..].char_indices();
+
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break start + 1 + i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, '"')) => {
+                        escapes.push(pattern.len());
+                        pattern.push('"')
+                    },
+                    Some((_, ch)) => { pattern.push('\\'); pattern.push(ch) },
+                    None => (),
+                },
+                Some((_, ch)) => pattern.push(ch),
+                None => return Err(ParseError {
+                    position: start,
+                    message: "Unterminated literal".into(),
+                }),
+            }
+        };
+        self.pos = end + 1;
+
+        let hir = regex_syntax::ParserBuilder::new()
+            .allow_invalid_utf8(self.bytes)
+            .build()
+            .parse(&pattern)
+            .map_err(|e| {
+                use regex_syntax::Error::*;
+
+                let (offset, message) = match &e {
+                    Parse(e) => (e.span().start.offset, e.kind().to_string()),
+                    Translate(e) => (e.span().start.offset, e.kind().to_string()),
+                    _ => (0, e.to_string()),
+                };
+
+                let shift = escapes.iter().filter(|&&i| i < offset).count();
+
+                ParseError { position: start + 1 + offset + shift, message }
+            })?;
+
+        let regex =
+            if self.bytes { Regex::try_from_bytes(hir) }
+            else { super::from_hir(hir, false) };
+
+        regex.map_err(|message| ParseError { position: start, message: message.into() })
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use sana_core::{Error, Rule, RuleSet, Shadowed};
+use sana_core::regex::Regex;
+
+use common::rules;
+
+#[test]
+fn shadowed_keyword() {
+    let ruleset = rules(&[
+        ("[a-z]+", "Ident", 1),
+        ("in", "In", 0),
+        ("int", "Int", 2),
+    ]);
+
+    let analysis = ruleset.analyze();
+
+    assert_eq!(
+        analysis.shadowed,
+        vec![Shadowed { rule: 1, by: Some((0, "in".into())) }]
+    );
+}
+
+#[test]
+fn empty_rule() {
+    let mut ruleset = rules(&[("[a-z]+", "Ident", 0)]);
+    ruleset.rules.push(Rule { regex: Regex::Nothing, priority: 0, action: "Never" });
+
+    let analysis = ruleset.analyze();
+
+    assert_eq!(analysis.shadowed, vec![Shadowed { rule: 1, by: None }]);
+}
+
+#[test]
+fn no_shadowed_rules() {
+    let ruleset = rules(&[
+        ("[a-z]+", "Ident", 0),
+        ("in", "In", 1),
+    ]);
+
+    let analysis = ruleset.analyze();
+
+    assert!(analysis.shadowed.is_empty());
+    assert_eq!(analysis.automata.states.len(), ruleset.construct_dfa().unwrap().states.len());
+}
+
+#[test]
+fn ambiguity_witness() {
+    let ruleset = rules(&[
+        ("[a-z]+", "Ident", 0),
+        ("let|le", "Kw", 0),
+    ]);
+
+    assert_eq!(
+        ruleset.construct_dfa().unwrap_err(),
+        Error::AmbiguityError(0, 1, "le".into())
+    );
+}
+
+#[test]
+fn all_conflicts() {
+    let ruleset = rules(&[
+        ("[a-z]+", "Ident", 0),
+        ("let", "Let", 0),
+        ("[0-9]+", "Number", 0),
+        ("0x[0-9a-f]+|[0-9]", "Hex", 0),
+        ("if", "If", 1),
+    ]);
+
+    let analysis = ruleset.analyze();
+
+    assert_eq!(
+        analysis.conflicts,
+        vec![
+            Error::AmbiguityError(2, 3, "0".into()),
+            Error::AmbiguityError(0, 1, "let".into()),
+        ]
+    );
+    assert_eq!(ruleset.construct_dfa().unwrap_err(), analysis.conflicts[0]);
+}
+
+#[test]
+fn trailing_context_length() {
+    let length = |trailing: &str| Regex::parse(trailing).unwrap().fixed_length();
+
+    assert_eq!(length("\"[^.]\""), Some(1));
+    assert_eq!(length("\"ab|cd\""), Some(2));
+    assert_eq!(length("\"[a-z]+\" & \"...\""), Some(3));
+    assert_eq!(length("\"a|bc\""), None);
+    assert_eq!(length("\"a+\""), None);
+    assert_eq!(length("\"a\" & \"b\""), None);
+
+    let rule = Rule::new(Regex::literal_str("a"), 0, ()).followed_by(Regex::literal_str("b"));
+    let ruleset = RuleSet { rules: vec![rule] };
+    let dfa = ruleset.construct_dfa().unwrap();
+    let end = "ab".chars().try_fold(0, |st, ch| dfa.transite(st, ch));
+
+    assert!(matches!(end.and_then(|st| dfa.get(st)), Some(sana_core::automata::State::Action(()))));
+}
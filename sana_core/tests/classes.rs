@@ -0,0 +1,66 @@
+mod common;
+
+use sana_core::automata::{Automata, CharRange};
+
+fn compile(rules: &[(&str, &'static str, usize)]) -> Automata<&'static str> {
+    common::rules(rules).construct_dfa().unwrap()
+}
+
+#[test]
+fn identifiers() {
+    let dfa = compile(&[
+        ("[a-zA-Z_][a-zA-Z0-9_]*", "Ident", 0),
+        ("[0-9]+", "Int", 0),
+    ]);
+    let classes = dfa.char_classes();
+
+    // Letters with `_`, digits and everything else
+    assert_eq!(classes.len(), 3);
+    assert_eq!(classes.class('\0'), 0);
+    assert_eq!(classes.class('a'), classes.class('Z'));
+    assert_eq!(classes.class('a'), classes.class('_'));
+    assert_eq!(classes.class('0'), classes.class('9'));
+    assert_ne!(classes.class('a'), classes.class('0'));
+    assert_eq!(classes.class(' '), classes.class('ж'));
+    assert_eq!(classes.class(' '), classes.class(std::char::MAX));
+}
+
+#[test]
+fn non_ascii() {
+    let dfa = compile(&[("[а-я]+", "Word", 0), ("[ё\u{10000}]", "Other", 0)]);
+    let classes = dfa.char_classes();
+
+    assert_eq!(classes.len(), 3);
+    assert!(classes.ascii.iter().all(|&class| class == 0));
+    assert_eq!(classes.class('ё'), classes.class('\u{10000}'));
+    assert_ne!(classes.class('ё'), classes.class('ж'));
+    assert_eq!(classes.class('\u{D7FF}'), classes.class('\u{E000}'));
+    assert_eq!(classes.char_ranges(), vec![
+        (CharRange::new('\0', 'Я'), 0),
+        (CharRange::new('а', 'я'), 1),
+        (CharRange::new('\u{450}', '\u{450}'), 0),
+        (CharRange::new('ё', 'ё'), 2),
+        (CharRange::new('\u{452}', '\u{FFFF}'), 0),
+        (CharRange::new('\u{10000}', '\u{10000}'), 2),
+        (CharRange::new('\u{10001}', std::char::MAX), 0),
+    ]);
+}
+
+#[test]
+fn same_transitions() {
+    let dfa = compile(&[
+        ("[a-z]+", "Ident", 0),
+        ("if|else", "Keyword", 1),
+        ("[0-9]+(\\.[0-9]+)?", "Number", 0),
+        ("\\.\\.", "Range", 0),
+    ]);
+    let classes = dfa.char_classes();
+
+    for st in 0..dfa.states.len() {
+        for ch in (0..0x500).filter_map(std::char::from_u32) {
+            let rep = classes.representatives[classes.class(ch)];
+
+            assert_eq!(dfa.transite(st, ch), dfa.transite(st, rep));
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use sana_core::{Rule, RuleSet};
+use sana_core::regex::Regex;
+
+use std::convert::TryFrom;
+
+/// Build a rule set from regular expressions in the syntax of `regex_syntax`,
+/// their actions and priorities
+pub fn rules(rules: &[(&str, &'static str, usize)]) -> RuleSet<&'static str> {
+    let rules = rules.iter()
+        .map(|(regex, act, prio)|  {
+            let hir = regex_syntax::Parser::new()
+                .parse(regex).unwrap();
+            let regex = Regex::try_from(hir).unwrap();
+
+            Rule {
+                regex,
+                priority: *prio,
+                action: *act
+            }
+        })
+        .collect();
+
+    RuleSet { rules }
+}
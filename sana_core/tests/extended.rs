@@ -0,0 +1,86 @@
+use sana_core::RuleSet;
+use sana_core::regex::{ParseError, Regex};
+use sana_core::ir::{Ir, Vm, VmResult};
+
+use std::convert::TryFrom;
+
+fn plain(regex: &str) -> Regex {
+    let hir = regex_syntax::Parser::new()
+        .parse(regex).unwrap();
+
+    Regex::try_from(hir).unwrap()
+}
+
+fn error(regex: &str) -> (usize, String) {
+    let ParseError { position, message } = Regex::parse(regex).unwrap_err();
+
+    (position, message)
+}
+
+#[test]
+fn operators() {
+    let a = || plain("a");
+    let b = || plain(r"\d+");
+
+    assert_eq!(Regex::parse(r#""a""#).unwrap(), a());
+    assert_eq!(Regex::parse(r#""a" | "\d+""#).unwrap(), Regex::Or(vec![a(), b()]));
+    assert_eq!(Regex::parse(r#""a"&"\d+""#).unwrap(), Regex::And(vec![a(), b()]));
+    assert_eq!(Regex::parse(r#""a" . "\d+""#).unwrap(), Regex::Concat(vec![a(), b()]));
+    assert_eq!(Regex::parse(r#"!!"a""#).unwrap(), Regex::Not(Box::new(Regex::Not(Box::new(a())))));
+}
+
+#[test]
+fn precedence() {
+    let a = || plain("a");
+    let b = || plain("b");
+    let c = || plain("c");
+
+    assert_eq!(
+        Regex::parse(r#""a" | "b" . "c""#).unwrap(),
+        Regex::Or(vec![a(), Regex::Concat(vec![b(), c()])])
+    );
+    assert_eq!(
+        Regex::parse(r#"("a" | "b") . !"c""#).unwrap(),
+        Regex::Concat(vec![Regex::Or(vec![a(), b()]), Regex::Not(Box::new(c()))])
+    );
+}
+
+#[test]
+fn escaped_quote() {
+    assert_eq!(Regex::parse(r#""\"[a-z]\"""#).unwrap(), plain(r#""[a-z]""#));
+    assert_eq!(Regex::parse(r#""\\""#).unwrap(), plain(r"\\"));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("   ").0, 3);
+    assert_eq!(error(r#""a" |"#), (5, "Unexpected end of regex".into()));
+    assert_eq!(error(r#"("a" | "b""#), (0, "Unclosed parenthesis".into()));
+    assert_eq!(error(r#""a")"#), (3, "Unmatched closing parenthesis".into()));
+    assert_eq!(error(r#""a" "b""#), (4, "Expected an operator".into()));
+    assert_eq!(error(r#""a" | x"#), (6, "Expected a literal or a parenthesis".into()));
+    assert_eq!(error(r#""a" | "abc"#), (6, "Unterminated literal".into()));
+
+    // The position points to the unclosed class inside of the literal
+    assert_eq!(error(r#""a" | "\"x[a-""#).0, 10);
+}
+
+#[test]
+fn rule_set_builder() {
+    let mut ruleset = RuleSet::new();
+    ruleset
+        .push_regex(r#""[a-z]+" & !"let""#, 0, "Ident").unwrap()
+        .push_token("let", 0, "Let")
+        .push_token(" ", 0, "Space");
+
+    assert!(ruleset.push_regex("(", 0, "Bad").is_err());
+
+    let dfa = ruleset.construct_dfa().unwrap();
+    let code = Ir::from_automata(dfa).flatten();
+    let mut vm = Vm::new(&code, "let lets");
+
+    assert_eq!(vm.run(), VmResult::Action { start: 0, end: 3, action: "Let" });
+    assert_eq!(vm.run(), VmResult::Action { start: 3, end: 4, action: "Space" });
+    assert_eq!(vm.run(), VmResult::Action { start: 4, end: 8, action: "Ident" });
+    assert_eq!(vm.run(), VmResult::Eoi);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use sana_core::automata::{Automata, CharRange, State};
+use sana_core::ir::{Ir, Vm, VmResult};
+
+use std::collections::BTreeMap;
+
+fn compile(rules: &[(&str, &'static str, usize)]) -> Automata<&'static str> {
+    common::rules(rules).construct_dfa().unwrap()
+}
+
+fn tokens(dfa: Automata<&'static str>, input: &str) -> Vec<VmResult<&'static str>> {
+    let code = Ir::from_automata(dfa).flatten();
+    let mut vm = Vm::new(&code, input);
+
+    let mut results = vec![];
+    loop {
+        let res = vm.run();
+        results.push(res);
+
+        match res {
+            VmResult::Eoi => break,
+            VmResult::Error { .. } => vm.cursor.shift(),
+            _ => (),
+        }
+    }
+
+    results
+}
+
+#[test]
+fn merge_equivalent_states() {
+    // 0 -a-> 1, 0 -b-> 2, 1 and 2 accept "c" with the same action
+    let mut dfa = Automata { states: vec![State::Normal], edges: BTreeMap::new() };
+    dfa.insert_state(State::Normal);
+    dfa.insert_state(State::Normal);
+    dfa.insert_state(State::Action("C"));
+    dfa.insert_state(State::Action("C"));
+    dfa.insert_edge(0, 1, CharRange::new('a', 'a'));
+    dfa.insert_edge(0, 2, CharRange::new('b', 'b'));
+    dfa.insert_edge(1, 3, CharRange::new('c', 'c'));
+    dfa.insert_edge(2, 4, CharRange::new('c', 'c'));
+
+    let min = dfa.minimize();
+
+    assert_eq!(min.states, vec![State::Normal, State::Normal, State::Action("C")]);
+    assert_eq!(min.transite(0, 'a'), Some(1));
+    assert_eq!(min.transite(0, 'b'), Some(1));
+    assert_eq!(min.transite(1, 'c'), Some(2));
+}
+
+#[test]
+fn distinct_actions() {
+    let mut dfa = Automata { states: vec![State::Normal], edges: BTreeMap::new() };
+    dfa.insert_state(State::Action("A"));
+    dfa.insert_state(State::Action("B"));
+    dfa.insert_edge(0, 1, CharRange::new('a', 'a'));
+    dfa.insert_edge(0, 2, CharRange::new('b', 'b'));
+
+    let min = dfa.minimize();
+
+    assert_eq!(min.states.len(), 3);
+    assert_eq!(min.transitions_from(0).count(), 2);
+}
+
+#[test]
+fn minimal_dfa() {
+    let dfa = compile(&[("a+", "A", 0)]).minimize();
+
+    // Initial, dead and accepting states
+    assert_eq!(dfa.states.len(), 3);
+    assert_eq!(dfa.minimize().states.len(), 3);
+}
+
+#[test]
+fn same_tokens() {
+    let rules = &[
+        ( "[a-z]+", "Word", 0 ),
+        ( "(if|in|for)", "Keyword", 1 ),
+        ( "[0-9]+|0x[0-9a-f]+", "Number", 0 ),
+        ( "(ab|cb)(ab|cb)", "Pairs", 2 ),
+        ( "[ \t\n]+", "Whitespace", 0 ),
+    ];
+    let input = "for x in abcb 0x1f 12 ifx\t!# cbab";
+
+    let dfa = compile(rules);
+    let min = dfa.minimize();
+
+    assert!(min.states.len() <= dfa.states.len());
+    assert_eq!(tokens(min, input), tokens(dfa, input));
+}
+
+#[test]
+fn terminal_node() {
+    let dfa = compile(&[("[a-z]+", "Word", 0)]).minimize();
+    let terminal = dfa.find_terminal_node();
+
+    assert_eq!(dfa.try_find_terminal_node(), Some(terminal));
+    assert_eq!(dfa.transite(terminal, 'a'), Some(terminal));
+}
+
+#[test]
+fn no_dead_state() {
+    let dfa = compile(&[("[a-z]+", "Word", 1), ("(?s).+", "Rest", 0)]).minimize();
+    assert_eq!(dfa.try_find_terminal_node(), None);
+
+    assert_eq!(tokens(dfa, "ab\n"), vec![
+        VmResult::Action { start: 0, end: 3, action: "Rest" },
+        VmResult::Eoi,
+    ]);
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use sana_core::automata::Automata;
+use sana_core::ir::{Block, Ir, Op, Passes, Vm, VmResult};
+
+fn compile(rules: &[(&str, &'static str, usize)]) -> Automata<&'static str> {
+    common::rules(rules).construct_dfa().unwrap().minimize()
+}
+
+fn tokens(code: &[Op<&'static str>], input: &str) -> Vec<VmResult<&'static str>> {
+    let mut vm = Vm::new(code, input);
+
+    let mut results = vec![];
+    loop {
+        let res = vm.run();
+        results.push(res);
+
+        match res {
+            VmResult::Eoi => break,
+            VmResult::Error { .. } => vm.cursor.shift(),
+            _ => (),
+        }
+    }
+
+    results
+}
+
+fn optimized(dfa: &Automata<&'static str>, passes: Passes) -> Ir<&'static str> {
+    let mut ir = Ir::from_automata(dfa.clone());
+    ir.optimize(passes);
+
+    ir
+}
+
+fn ops<'a>(ir: &'a Ir<&'static str>) -> Vec<&'a Op<&'static str>> {
+    ir.blocks.iter()
+        .flat_map(|block| match block {
+            Block::Block(ops) | Block::Func(ops) => ops,
+        })
+        .collect()
+}
+
+const RULES: &[(&str, &str, usize)] = &[
+    ("[a-zA-Z_][a-zA-Z0-9_]*", "Ident", 0),
+    ("let|if|else", "Keyword", 1),
+    ("[0-9]+", "Integer", 0),
+    ("[0-9]+\\.[0-9]+", "Float", 0),
+    ("0x[0-9a-f]+", "Hex", 1),
+    ("\"([^\"\\\\]|\\\\.)*\"", "String", 0),
+    ("[ \t\n]+", "Whitespace", 0),
+    ("[=;]", "Punct", 0),
+    ("//[^\n]*", "Comment", 0),
+    ("/", "Slash", 0),
+];
+
+const INPUTS: &[&str] = &[
+    "",
+    "let answer = 42;",
+    "if x1 else 0x1f 0xg 3.14 3. \"a\\\"b\" \"open",
+    "// comment\n/ x",
+    "жx 1.5.6",
+];
+
+#[test]
+fn passes_keep_matches() {
+    let dfa = compile(RULES);
+    let code = Ir::from_automata(dfa.clone()).flatten();
+
+    let single = |set: fn(&mut Passes)| {
+        let mut passes = Passes::NONE;
+        set(&mut passes);
+
+        passes
+    };
+    let passes = [
+        Passes::ALL,
+        single(|p| p.thread_jumps = true),
+        single(|p| p.dedup_blocks = true),
+        single(|p| p.coalesce_ranges = true),
+        single(|p| p.remove_dead_blocks = true),
+    ];
+
+    for passes in passes {
+        let opt_code = optimized(&dfa, passes).flatten();
+
+        for input in INPUTS {
+            assert_eq!(tokens(&opt_code, input), tokens(&code, input), "{:?} {:?}", passes, input);
+        }
+    }
+}
+
+#[test]
+fn no_passes() {
+    let dfa = compile(RULES);
+    let ir = optimized(&dfa, Passes::NONE);
+
+    assert_eq!(ir.flatten(), Ir::from_automata(dfa).flatten());
+}
+
+#[test]
+fn smaller_code() {
+    let dfa = compile(RULES);
+    let ir = Ir::from_automata(dfa.clone());
+    let opt = optimized(&dfa, Passes::ALL);
+
+    assert!(opt.blocks.len() < ir.blocks.len());
+    assert!(ops(&opt).len() < ops(&ir).len());
+
+    // `jnm` to the terminal block followed by `jump` become `jm`
+    let jnm = ops(&opt).into_iter()
+        .filter(|op| matches!(op, Op::JumpNotMatches { .. }))
+        .count();
+    assert_eq!(jnm, 0);
+}
+
+#[test]
+fn coalesce_ranges() {
+    let mut ir = Ir {
+        blocks: vec![
+            Block::Func(vec![
+                Op::JumpMatches { from: 'c', to: 'd', on_success: 1 },
+                Op::LoopMatches { from: '0', to: '4' },
+                Op::JumpMatches { from: 'a', to: 'b', on_success: 1 },
+                Op::LoopMatches { from: '5', to: '9' },
+                Op::JumpMatches { from: '\u{E000}', to: '\u{FFFF}', on_success: 1 },
+                Op::JumpMatches { from: '\u{D000}', to: '\u{D7FF}', on_success: 1 },
+                Op::Halt,
+            ]),
+            Block::Block(vec![Op::Shift, Op::Set("A"), Op::Halt]),
+        ]
+    };
+    ir.optimize(Passes { coalesce_ranges: true, ..Passes::NONE });
+
+    assert_eq!(ir.flatten(), vec![
+        Op::LoopMatches { from: '0', to: '9' },
+        Op::JumpMatches { from: 'a', to: 'd', on_success: 4 },
+        Op::JumpMatches { from: '\u{D000}', to: '\u{FFFF}', on_success: 4 },
+        Op::Halt,
+        Op::Shift,
+        Op::Set("A"),
+        Op::Halt,
+    ]);
+}
+
+#[test]
+fn dedup_blocks() {
+    let mut ir = Ir {
+        blocks: vec![
+            Block::Func(vec![
+                Op::JumpMatches { from: 'a', to: 'a', on_success: 1 },
+                Op::JumpMatches { from: 'b', to: 'b', on_success: 2 },
+                Op::Halt,
+            ]),
+            Block::Block(vec![Op::Shift, Op::Set("A"), Op::Halt]),
+            Block::Block(vec![Op::Shift, Op::Set("A"), Op::Halt]),
+        ]
+    };
+    ir.optimize(Passes::ALL);
+
+    assert_eq!(ir.flatten(), vec![
+        Op::JumpMatches { from: 'a', to: 'b', on_success: 2 },
+        Op::Halt,
+        Op::Shift,
+        Op::Set("A"),
+        Op::Halt,
+    ]);
+}
+
+#[test]
+fn dead_blocks() {
+    // The terminal block added to a DFA without a dead state is unreachable
+    let dfa = compile(&[("(?s).+", "Rest", 0)]);
+    let ir = Ir::from_automata(dfa.clone());
+    let opt = optimized(&dfa, Passes::ALL);
+
+    assert!(opt.blocks.len() < ir.blocks.len());
+    assert_eq!(tokens(&opt.flatten(), "abc"), tokens(&ir.flatten(), "abc"));
+}
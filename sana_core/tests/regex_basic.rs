@@ -45,7 +45,9 @@ fn dfa_match<T>(dfa: &Automata<T>, input: &str) -> bool {
     matches!(dfa.get(state_ix), Some(&State::Action(_)))
 }
 
-pub fn regexps() -> Vec<(&'static str, &'static str, Vec<Option<(usize, usize)>>)> {
+type Test = (&'static str, &'static str, Vec<Option<(usize, usize)>>);
+
+pub fn regexps() -> Vec<Test> {
     vec![
         ( r"abracadabra$", r"abracadabracadabra", vec![Some((7, 18))] ), 
         ( r"a...b", r"abababbb", vec![Some((2, 7))] ), 
@@ -253,3 +255,22 @@ pub fn regexps() -> Vec<(&'static str, &'static str, Vec<Option<(usize, usize)>>
         ( r"\\000", r"\000", vec![Some((0, 4))] ),
     ]
 }
+
+#[test]
+fn large_repetition() {
+    let hir = regex_syntax::Parser::new()
+        .parse("[a-z]{1,1000}").unwrap();
+    let regex = Regex::try_from(hir).unwrap();
+
+    let dfa = Rule { regex, priority: 0, action: 0 }
+        .construct_dfa();
+
+    // One state per counted character, the initial state and the dead state
+    assert_eq!(dfa.states.len(), 1002);
+
+    let input: String = "z".repeat(1000);
+    assert!(dfa_match(&dfa, &input));
+    assert!(dfa_match(&dfa, &input[..1]));
+    assert!(!dfa_match(&dfa, ""));
+    assert!(!dfa_match(&dfa, &"z".repeat(1001)));
+}
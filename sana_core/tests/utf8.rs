@@ -0,0 +1,77 @@
+mod common;
+
+use sana_core::automata::Automata;
+use sana_core::ir::{self, Cursor, Ir, Vm, VmResult};
+
+fn compile(rules: &[(&str, &'static str, usize)]) -> Automata<&'static str> {
+    common::rules(rules).construct_dfa().unwrap()
+}
+
+fn assert_same_tokens(rules: &[(&str, &'static str, usize)], input: &str) {
+    let dfa = compile(rules);
+
+    let code = Ir::from_automata(dfa.clone()).flatten();
+    let utf8_code = Ir::from_automata(dfa.to_utf8()).flatten();
+
+    let mut vm = Vm::new(&code, input);
+    let mut cursor = Cursor::new(input);
+
+    loop {
+        let gold = vm.run();
+        let res = ir::lex_utf8(&mut cursor, |bytes| ir::execute(&utf8_code, bytes));
+
+        assert_eq!(gold, res);
+        assert_eq!(vm.cursor.position(), cursor.position());
+
+        if gold == VmResult::Eoi { break }
+        if let VmResult::Error { .. } = gold {
+            // Skip the bad char
+            vm.cursor.shift();
+            cursor.shift();
+        }
+    }
+}
+
+#[test]
+fn unicode() {
+    let input = "żółć 🦀🦀 Жук ~abc ###! źle? 😀 żart";
+
+    assert_same_tokens(unicode_tokens(), input)
+}
+
+#[test]
+fn shared_prefixes() {
+    let rules = &[
+        ("é", "ECute", 0),
+        ("è+", "EGrave", 0),
+        ("[ࠀ-ࠅ]", "Low", 0),
+        ("[ࠐ-\u{fff}]", "High", 0),
+        (" ", "Space", 0),
+    ];
+
+    assert_same_tokens(rules, "éèè ࠀ ࠆ ࠐ\u{fff} ê é");
+}
+
+#[test]
+fn lowered_states() {
+    let dfa = compile(&[("[α-ω]+", "Greek", 0)]);
+    let utf8 = dfa.to_utf8();
+
+    // `α..=ο` and `π..=ω` have different first bytes, so there are two
+    // intermediate states. Both Greek states share them, since they have
+    // the same transitions
+    assert_eq!(utf8.states.len(), dfa.states.len() + 2);
+}
+
+pub fn unicode_tokens() -> &'static [(&'static str, &'static str, usize)] {
+    &[
+        ( r"[ \t\n\f]+", "Whitespace", 0 ),
+        ( "~[a-z][a-z]+", "Address", 0 ),
+        ( "🦀+", "Rustaceans", 1 ),
+        ( "[ąęśćżźńół]+", "Polish", 1 ),
+        ( r"[a-ząęśćżźńół]+", "Word", 0 ),
+        ( r"[Ѐ-ӿ]+", "Cyrillic", 0 ),
+        ( r"([#@!\\?][#@!\\?][#@!\\?][#@!\\?])+", "WhatTheHeck", 0 ),
+        ( r"\p{Emoji_Presentation}", "Emoji", 0 ),
+    ]
+}
@@ -1,52 +1,662 @@
 use syn::Ident;
 use heck::ShoutySnakeCase;
 use proc_macro2::TokenStream;
-use proc_macro_error::abort;
+use proc_macro_error::{abort_if_dirty, emit_error, Diagnostic, Level};
 use quote::{format_ident, quote};
 
-use sana_core::ir::{Op, Ir};
+use sana_core::Analysis;
+use sana_core::automata::Automata;
+use sana_core::ir::{Op, Ir, Passes};
 use crate::{SanaSpec, Backend};
+use crate::parser::ModeChange;
+
+/// Lower the DFA into the IR and run the optimization passes
+fn optimize(dfa: Automata<usize>, passes: Passes) -> Ir<usize> {
+    let mut ir = Ir::from_automata(dfa);
+    ir.optimize(passes);
+
+    ir
+}
+
+/// Two conflicting variants and the shortest string they both match
+type Conflict = (usize, usize, String);
+
+/// Collect the conflicts of all modes, each pair of variants only once
+fn collect_conflicts(spec: &SanaSpec, analyses: &[Analysis<usize>]) -> Vec<Conflict> {
+    let mut conflicts: Vec<Conflict> = vec![];
+
+    for (mode, analysis) in spec.modes.iter().zip(analyses) {
+        for sana_core::Error::AmbiguityError(ix, i, witness) in &analysis.conflicts {
+            let (first, second) = (mode.rules.rules[*ix].action, mode.rules.rules[*i].action);
+
+            // Both rules are skipped, so it does not matter which one is chosen
+            if spec.variants[first].skip && spec.variants[second].skip { continue }
+
+            if !conflicts.iter().any(|c| (c.0, c.1) == (first, second)) {
+                conflicts.push((first, second, witness.clone()))
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Emit one diagnostic per variant conflicting with the variants before it
+fn report_conflicts(spec: &SanaSpec, conflicts: &[Conflict]) {
+    let mut seconds: Vec<_> = conflicts.iter()
+        .map(|c| c.1)
+        .collect();
+    seconds.sort_unstable();
+    seconds.dedup();
+
+    for second in seconds {
+        let second_var = &spec.variants[second].ident;
+        let mut diagnostic = Diagnostic::spanned(second_var.span(), Level::Error, "Ambiguous rule".into());
+
+        for (first, _, witness) in conflicts.iter().filter(|c| c.1 == second) {
+            let first = &spec.variants[*first].ident;
+            diagnostic = diagnostic
+                .note(format!("Both `{}` and `{}` match {:?}", first, second_var, witness))
+                .span_note(first.span(), format!("Resolve conflicts with {}", first));
+        }
+
+        diagnostic.emit()
+    }
+}
+
+/// Emit an error for each variant that is never produced in any of its modes
+fn report_shadowed(spec: &SanaSpec, analyses: &[Analysis<usize>]) {
+    let mut reachable = vec![false; spec.variants.len()];
+    let mut shadowed_by = vec![None; spec.variants.len()];
+
+    for (mode, analysis) in spec.modes.iter().zip(analyses) {
+        for (i, rule) in mode.rules.rules.iter().enumerate() {
+            match analysis.shadowed.iter().find(|sh| sh.rule == i) {
+                Some(shadowed) => {
+                    let by = shadowed.by.as_ref()
+                        .map(|(by, example)| (mode.rules.rules[*by].action, example.clone()));
+
+                    shadowed_by[rule.action].get_or_insert(by);
+                },
+                None => reachable[rule.action] = true,
+            }
+        }
+    }
+
+    for (action, by) in shadowed_by.into_iter().enumerate() {
+        if reachable[action] || spec.variants[action].allow_unreachable { continue }
+
+        let variant = &spec.variants[action].ident;
+
+        match by {
+            Some(Some((by, example))) => {
+                let by = &spec.variants[by].ident;
+
+                emit_error!(
+                    variant, "Unreachable rule";
+                    note = "`{}` is always chosen instead, for example on {:?}", by, example;
+                    help = "Set a higher priority for `{}` or add `allow_unreachable` to its rule", variant
+                )
+            },
+            Some(None) => emit_error!(
+                variant, "Unreachable rule";
+                note = "The rule does not match any string";
+                help = "Remove `{}` or add `allow_unreachable` to its rule", variant
+            ),
+            None => (),
+        }
+    }
+}
 
 pub(crate) fn generate(spec: SanaSpec) -> TokenStream {
-    let dfa = match spec.rules.construct_dfa() {
-        Ok(dfa) => dfa,
-        Err(sana_core::Error::AmbiguityError(ix, i)) =>
-            abort!(spec.variants[i].span(), "Ambiguous rule";
-            note = spec.variants[ix].span() => "Resolve conflicts with {}", spec.variants[ix]),
-    };
+    let analyses: Vec<_> = spec.modes.iter()
+        .map(|mode| mode.rules.analyze())
+        .collect();
+
+    report_conflicts(&spec, &collect_conflicts(&spec, &analyses));
+    abort_if_dirty();
 
-    let ir = Ir::from_automata(dfa);
+    report_shadowed(&spec, &analyses);
 
-    let enum_ident = spec.enum_ident;
+    let enum_ident = &spec.enum_ident;
     let enum_const_name = enum_ident.to_string()
         .to_shouty_snake_case();
-    let ir_var = format_ident!("_{}_IR", enum_const_name);
-    let ir_code = generate_ir(&enum_ident, &ir, &spec.variants);
+    let error = &spec.terminal;
+
+    let input =
+        if spec.bytes { quote!{ [u8] } }
+        else { quote!{ str } };
+
+    let mut lexes = vec![];
+    let mut restricted = vec![];
+    let mut items = vec![];
+    for (k, (mode, analysis)) in spec.modes.iter().zip(analyses).enumerate() {
+        let dfa = analysis.automata;
+        let dfa =
+            if spec.utf8 { dfa.to_utf8() }
+            else { dfa };
+
+        let dfa =
+            if spec.minimize { dfa.minimize() }
+            else { dfa };
+
+        // The names of the initial mode items have no suffix
+        let suffix =
+            if k == 0 { String::new() }
+            else { format!("_{}", mode.name.to_shouty_snake_case()) };
+        let ir_var = format_ident!("_{}_IR{}", enum_const_name, suffix);
+        let lexer_name = format_ident!("_{}_LEXER{}", enum_const_name, suffix);
+        let dfa_var = format_ident!("_{}_DFA{}", enum_const_name, suffix);
+        let table_var = format_ident!("_{}_TABLE{}", enum_const_name, suffix);
+
+        if spec.restricted {
+            let tagged = generate_tagged_dfa(&mode.rules.construct_tagged_dfa());
+            items.push(quote! {
+                #[doc(hidden)]
+                static #dfa_var: sana::restricted::Dfa = #tagged;
+            });
+            restricted.push(skip_loop(&spec, cut_trailing(&spec, quote! {
+                sana::restricted::run(&#dfa_var, cursor, allowed)
+            })));
+        }
+
+        let lex = match spec.backend {
+            Backend::Vm => {
+                let ir_code = generate_ir(&optimize(dfa, spec.passes));
 
-    let lexer_name = format_ident!("_{}_LEXER", enum_const_name);
-    let bytecode = analyze_ir(&ir);
-    let rust_code = compile_bytecode(bytecode, &enum_ident, &spec.variants);
-    let error = spec.terminal;
+                items.push(quote! {
+                    #[doc(hidden)]
+                    const #ir_var: &'static [sana::ir::Op<usize>] = #ir_code;
+                });
+
+                quote! { sana::ir::execute(#ir_var, cursor) }
+            },
+            Backend::Rust => {
+                let bytecode = analyze_ir(&optimize(dfa, spec.passes));
+                let rust_code = compile_bytecode(bytecode);
+
+                items.push(generate_lexer(&lexer_name, rust_code));
+
+                quote! { #lexer_name::new().run(cursor) }
+            },
+            Backend::Table => {
+                let table = generate_table(&dfa);
 
-    let uses_vm = spec.backend == Backend::Vm;
+                items.push(quote! {
+                    #[doc(hidden)]
+                    static #table_var: sana::table::Table = #table;
+                });
+
+                quote! { sana::table::run(&#table_var, cursor) }
+            },
+        };
+
+        let lex =
+            if spec.utf8 { quote! { sana::ir::lex_utf8(cursor, |cursor| #lex) } }
+            else { lex };
+
+        lexes.push(skip_loop(&spec, cut_trailing(&spec, lex)))
+    }
+
+    let lex = &lexes[0];
+    let modes_impl = generate_modes(&spec, &lexes, &input);
+    let from_action = generate_from_action(&spec);
+    let index = generate_index(&spec);
+    let from_index = generate_from_index(&spec);
+    let name = generate_metadata(&spec, |var| {
+        let name = var.ident.to_string();
+        quote! { #name }
+    });
+    let pattern = generate_metadata(&spec, |var| {
+        let pattern = &var.pattern;
+        quote! { #pattern }
+    });
+    let fixed_str = generate_metadata(&spec, |var| match &var.fixed {
+        Some(fixed) => quote! { ::core::option::Option::Some(#fixed) },
+        None => quote! { ::core::option::Option::None },
+    });
+    let all: Vec<_> = spec.variants.iter()
+        .filter(|var| !var.skip && !var.data)
+        .map(|var| &var.ident)
+        .collect();
+    let all_count = all.len();
+
+    let skips: Vec<_> = spec.variants.iter()
+        .enumerate()
+        .filter(|(_, var)| var.skip)
+        .map(|(i, _)| i)
+        .collect();
+    let allowed =
+        if skips.is_empty() { quote! { allowed } }
+        else { quote! { &|action: usize| matches!(action, #(#skips)|*) || allowed(action) } };
+    let indices = 0..restricted.len();
+
+    let extras = match &spec.extras {
+        Some(ty) => quote! { #ty },
+        None => quote! { () },
+    };
+
+    let (impl_generics, source, enum_type) = match &spec.lifetime {
+        Some(lt) => (quote! { <#lt> }, quote! { #lt }, quote! { #enum_ident<#lt> }),
+        None => (quote! { <'source> }, quote! { 'source }, quote! { #enum_ident }),
+    };
+
+    let restricted_impl = spec.restricted.then(|| quote! {
+        impl #impl_generics sana::restricted::Restricted<#source> for #enum_type {
+            fn lex_restricted(
+                mode: usize,
+                cursor: &mut sana::ir::Cursor<'_, #input>,
+                allowed: &dyn Fn(usize) -> bool,
+            ) -> sana::ir::VmResult<usize> {
+                let allowed = #allowed;
+
+                match mode {
+                    #(#indices => #restricted,)*
+                    _ => unreachable!(),
+                }
+            }
+        }
+    });
+
+    let generics = spec.lifetime.as_ref().map(|lt| quote! { <#lt> });
+    let count = spec.variants.iter().filter(|var| !var.skip).count();
+    let words = count.div_ceil(64);
+    let set_type = quote! { sana::TokenSet<#enum_type, #words> };
+
+    // The alias is only generated on request, so it can't collide with the
+    // user's items
+    let set_alias = spec.set.as_ref().map(|set_ident| {
+        let vis = &spec.vis;
+
+        quote! {
+            /// A set of token variants
+            #vis type #set_ident #generics = #set_type;
+        }
+    });
 
     quote! {
-        #[doc(hidden)]
-        const #ir_var: &'static [sana::ir::Op<#enum_ident>] = #ir_code;
+        #(#items)*
+
+        #set_alias
+
+        impl #generics #enum_type {
+            /// The number of variants
+            pub const COUNT: usize = #count;
+
+            /// The index of the variant, which is less than `COUNT`
+            pub const fn index(&self) -> usize {
+                #index
+            }
+
+            /// The unit variant with the given index
+            ///
+            /// Return `None` if the index is out of range or the variant
+            /// has a field
+            pub const fn from_index(index: usize) -> ::core::option::Option<Self> {
+                #from_index
+            }
+
+            /// All unit variants, in the order of declaration
+            pub const ALL: [Self; #all_count] = [#(#enum_ident::#all),*];
+
+            /// The name of the variant
+            pub const fn name(&self) -> &'static str {
+                #name
+            }
+
+            /// The regular expressions or the token strings of the variant
+            ///
+            /// The patterns of several rules are separated by ` | `.
+            /// The pattern of the error variant is empty.
+            pub const fn pattern(&self) -> &'static str {
+                #pattern
+            }
+
+            /// The string matched by the variant, if it has a single
+            /// `#[token]` rule
+            pub const fn fixed_str(&self) -> ::core::option::Option<&'static str> {
+                #fixed_str
+            }
+        }
+
+        impl #impl_generics sana::Sana<#source> for #enum_type {
+            type Input = #input;
+            type Extras = #extras;
+            type Set = #set_type;
 
-        impl sana::Sana for #enum_ident {
             const ERROR: Self = #enum_ident::#error;
-            const USES_VM: bool = #uses_vm;
 
-            fn ir() -> &'static [sana::ir::Op<Self>] { #ir_var }
-            fn lex<'input>(cursor: &mut sana::ir::Cursor<'input>) -> sana::ir::VmResult<Self> {
-                let mut lexer = #lexer_name::new();
-                lexer.run(cursor)
+            fn lex(cursor: &mut sana::ir::Cursor<'_, #input>) -> sana::ir::VmResult<usize> {
+                #lex
+            }
+
+            fn from_action(
+                action: usize,
+                slice: &#source #input,
+                extras: &mut Self::Extras,
+            ) -> ::core::option::Option<Self> {
+                #from_action
+            }
+
+            fn index(&self) -> usize {
+                #enum_ident::index(self)
+            }
+
+            #modes_impl
+        }
+
+        #restricted_impl
+    }
+}
+
+/// Generate the body of the inherent `index` method
+fn generate_index(spec: &SanaSpec) -> TokenStream {
+    let enum_ident = &spec.enum_ident;
+
+    let arms = spec.variants.iter()
+        .enumerate()
+        .filter(|(_, var)| !var.skip)
+        .map(|(i, var)| {
+            let ident = &var.ident;
+
+            if var.data { quote! { #enum_ident::#ident(..) => #i, } }
+            else { quote! { #enum_ident::#ident => #i, } }
+        });
+
+    quote! {
+        match self {
+            #(#arms)*
+        }
+    }
+}
+
+/// Generate a match on the variants with the given arm values
+fn generate_metadata<F>(spec: &SanaSpec, value: F) -> TokenStream
+where F: Fn(&crate::VariantSpec) -> TokenStream {
+    let enum_ident = &spec.enum_ident;
+
+    let arms = spec.variants.iter()
+        .filter(|var| !var.skip)
+        .map(|var| {
+            let ident = &var.ident;
+            let value = value(var);
+
+            if var.data { quote! { #enum_ident::#ident(..) => #value, } }
+            else { quote! { #enum_ident::#ident => #value, } }
+        });
+
+    quote! {
+        match self {
+            #(#arms)*
+        }
+    }
+}
+
+/// Generate the body of the inherent `from_index` method
+fn generate_from_index(spec: &SanaSpec) -> TokenStream {
+    let enum_ident = &spec.enum_ident;
+
+    let arms = spec.variants.iter()
+        .enumerate()
+        .filter(|(_, var)| !var.skip && !var.data)
+        .map(|(i, var)| {
+            let ident = &var.ident;
+
+            quote! { #i => ::core::option::Option::Some(#enum_ident::#ident), }
+        });
+
+    quote! {
+        match index {
+            #(#arms)*
+            _ => ::core::option::Option::None,
+        }
+    }
+}
+
+/// Generate the table of a DFA with the states tagged by all matching
+/// variants
+///
+/// The states that never lead to a match are removed
+fn generate_tagged_dfa(dfa: &Automata<Vec<usize>>) -> TokenStream {
+    use sana_core::automata::State;
+
+    let mut live = dfa.live_states();
+    // The initial state is kept even if nothing matches
+    live[0] = true;
+
+    let mut renumber = vec![None; dfa.states.len()];
+    let mut count = 0usize;
+    for (i, &is_live) in live.iter().enumerate() {
+        if is_live {
+            renumber[i] = Some(count);
+            count += 1;
+        }
+    }
+
+    let states = dfa.states.iter()
+        .enumerate()
+        .filter(|&(i, _)| live[i])
+        .map(|(i, state)| {
+            let accept = match state {
+                State::Action(actions) => actions.as_slice(),
+                State::Normal => &[],
+            };
+            let edges = dfa.transitions_from(i)
+                .filter_map(|(range, to)| {
+                    let to = renumber[to]?;
+                    let (start, end) = (range.start, range.end);
+
+                    Some(quote! { (#start, #end, #to) })
+                });
+
+            quote! {
+                sana::restricted::DfaState {
+                    accept: &[#(#accept),*],
+                    edges: &[#(#edges),*],
+                }
+            }
+        });
+
+    quote! {
+        sana::restricted::Dfa {
+            states: &[#(#states),*],
+        }
+    }
+}
+
+/// Generate the transition table of a DFA for the table backend
+///
+/// The chars are mapped to the equivalence classes of the DFA, see
+/// `Automata::char_classes`. The dead state is left out of the table.
+fn generate_table(dfa: &Automata<usize>) -> TokenStream {
+    use sana_core::automata::State;
+
+    let classes = dfa.char_classes();
+
+    // The dead state is removed, unless it is the initial one
+    let dead = dfa.try_find_terminal_node().filter(|&dead| dead != 0);
+    let states: Vec<_> = (0..dfa.states.len())
+        .filter(|&st| Some(st) != dead)
+        .collect();
+    let mut renumber = vec![None; dfa.states.len()];
+    for (i, &st) in states.iter().enumerate() {
+        renumber[st] = Some(i as u32);
+    }
+
+    let class_count = classes.len();
+    let transitions = states.iter()
+        .flat_map(|&st| classes.representatives.iter()
+            .map(move |&ch| dfa.transite(st, ch)))
+        .map(|to| match to.and_then(|to| renumber[to]) {
+            Some(to) => quote! { #to },
+            None => quote! { sana::table::DEAD },
+        });
+    let actions = states.iter()
+        .map(|&st| match dfa.states[st] {
+            State::Action(act) => quote! { ::core::option::Option::Some(#act) },
+            State::Normal => quote! { ::core::option::Option::None },
+        });
+    let ascii = classes.ascii.iter().map(|&class| class as u32);
+    let ranges = classes.ranges.iter()
+        .map(|(range, class)| {
+            let (start, end, class) = (range.start, range.end, *class as u32);
+
+            quote! { (#start, #end, #class) }
+        });
+
+    quote! {
+        sana::table::Table {
+            ascii: [#(#ascii),*],
+            ranges: &[#(#ranges),*],
+            class_count: #class_count,
+            transitions: &[#(#transitions),*],
+            actions: &[#(#actions),*],
+        }
+    }
+}
+
+/// Exclude the trailing contexts of the variants from the matches
+fn cut_trailing(spec: &SanaSpec, lex: TokenStream) -> TokenStream {
+    let arms: Vec<_> = spec.variants.iter()
+        .enumerate()
+        .filter(|(_, var)| var.trailing > 0)
+        .map(|(i, var)| {
+            let len = var.trailing;
+
+            quote! { #i => #len, }
+        })
+        .collect();
+
+    if arms.is_empty() { return lex }
+
+    quote! {
+        sana::ir::cut_trailing(#lex, cursor, |action: &usize| match *action {
+            #(#arms)*
+            _ => 0,
+        })
+    }
+}
+
+/// Wrap the lexer into a loop that consumes the matches of `#[skip]` rules
+fn skip_loop(spec: &SanaSpec, lex: TokenStream) -> TokenStream {
+    let skips: Vec<_> = spec.variants.iter()
+        .enumerate()
+        .filter(|(_, var)| var.skip)
+        .map(|(i, _)| i)
+        .collect();
+
+    if skips.is_empty() { return lex }
+
+    quote! {
+        loop {
+            match #lex {
+                sana::ir::VmResult::Action { action: #(#skips)|*, .. } => continue,
+                res => return res,
             }
         }
+    }
+}
+
+/// Generate the body of `Sana::from_action`
+fn generate_from_action(spec: &SanaSpec) -> TokenStream {
+    let enum_ident = &spec.enum_ident;
 
+    let arms = spec.variants.iter()
+        .enumerate()
+        .filter(|(_, var)| var.ident != spec.terminal && !var.skip)
+        .map(|(i, var)| {
+            let ident = &var.ident;
+            let call = var.parse.as_ref().map(|parse| {
+                let path = &parse.path;
+
+                if parse.extras { quote! { #path(slice, extras) } }
+                else { quote! { #path(slice) } }
+            });
+            let value = match (call, var.data) {
+                (Some(call), true) => quote! {
+                    sana::IntoToken::into_token(#call).map(#enum_ident::#ident)
+                },
+                (Some(call), false) => quote! {
+                    sana::IntoToken::into_token(#call).map(|()| #enum_ident::#ident)
+                },
+                (None, _) => quote! {
+                    ::core::option::Option::Some(#enum_ident::#ident)
+                },
+            };
+
+            quote! { #i => #value, }
+        });
+
+    // The slice and the extras are unused if no variant has a callback
+    // that takes them
+    let unused_slice = spec.variants.iter().all(|var| var.parse.is_none());
+    let unused_slice = if unused_slice { quote! { let _ = slice; } } else { quote! {} };
+    let unused_extras = !spec.variants.iter()
+        .any(|var| var.parse.as_ref().is_some_and(|p| p.extras));
+    let unused_extras = if unused_extras { quote! { let _ = extras; } } else { quote! {} };
+
+    quote! {
+        #unused_slice
+        #unused_extras
+
+        match action {
+            #(#arms)*
+            _ => ::core::option::Option::None,
+        }
+    }
+}
+
+/// Generate the mode-related items of the `Sana` impl
+fn generate_modes(spec: &SanaSpec, lexes: &[TokenStream], input: &TokenStream) -> TokenStream {
+    let modes_impl =
+        if spec.modes.len() > 1 {
+            let names = spec.modes.iter().map(|m| &m.name);
+            let indices = 0..lexes.len();
+
+            quote! {
+                const MODES: &'static [&'static str] = &[#(#names),*];
+
+                fn lex_mode(mode: usize, cursor: &mut sana::ir::Cursor<'_, #input>) -> sana::ir::VmResult<usize> {
+                    match mode {
+                        #(#indices => #lexes,)*
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+        else { quote! { } };
+
+    let (actions, changes): (Vec<_>, Vec<_>) = spec.variants.iter()
+        .enumerate()
+        .filter_map(|(i, var)| {
+            let change = match var.mode_change.as_ref()? {
+                ModeChange::Push(mode) => quote! { sana::ModeChange::Push(#mode) },
+                ModeChange::Pop => quote! { sana::ModeChange::Pop },
+                ModeChange::Switch(mode) => quote! { sana::ModeChange::Switch(#mode) },
+            };
+
+            Some((i, change))
+        })
+        .unzip();
+
+    if actions.is_empty() { return modes_impl }
+
+    quote! {
+        #modes_impl
+
+        fn mode_change(action: usize) -> ::core::option::Option<sana::ModeChange> {
+            match action {
+                #(#actions => ::core::option::Option::Some(#changes),)*
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
+}
+
+fn generate_lexer(lexer_name: &Ident, rust_code: TokenStream) -> TokenStream {
+    quote! {
         struct #lexer_name {
-            action: ::core::option::Option<#enum_ident>,
+            action: ::core::option::Option<usize>,
             end: usize,
         }
 
@@ -57,7 +667,8 @@ pub(crate) fn generate(spec: SanaSpec) -> TokenStream {
                 Self { action, end }
             }
 
-            fn run<'input>(&mut self, cursor: &mut sana::ir::Cursor<'input>) -> sana::ir::VmResult<#enum_ident> {
+            fn run<I>(&mut self, cursor: &mut sana::ir::Cursor<'_, I>) -> sana::ir::VmResult<usize>
+            where I: sana::ir::Input + ?Sized {
                 self.action = None;
 
                 if cursor.is_eoi() {
@@ -66,8 +677,8 @@ pub(crate) fn generate(spec: SanaSpec) -> TokenStream {
 
                 let start = cursor.position();
 
-                // l0 is the entry point
-                self._l0(cursor);
+                // The state machine starts at l0
+                self._machine(cursor);
 
                 if self.action.is_none() && !cursor.is_eoi() {
                     return sana::ir::VmResult::Error {
@@ -91,7 +702,7 @@ pub(crate) fn generate(spec: SanaSpec) -> TokenStream {
     }
 }
 
-fn generate_ir(enum_ident: &Ident, ir: &Ir<usize>, variants: &[Ident]) -> TokenStream {
+fn generate_ir(ir: &Ir<usize>) -> TokenStream {
     let code = ir.flatten();
     let mut ops = vec![];
 
@@ -123,10 +734,8 @@ fn generate_ir(enum_ident: &Ident, ir: &Ir<usize>, variants: &[Ident]) -> TokenS
             Op::Jump(loc) => quote! {
                 sana::ir::Op::Jump(#loc)
             },
-            Op::Set(act) => {
-                let var = &variants[act];
-
-                quote! { sana::ir::Op::Set(#enum_ident::#var) }
+            Op::Set(act) => quote! {
+                sana::ir::Op::Set(#act)
             },
             Op::Halt => quote! {
                 sana::ir::Op::Halt
@@ -307,7 +916,7 @@ fn optimize_match(match_stmt: &mut Match) {
         let mut arms = match_stmt.arms.clone();
 
         // sort by block
-        arms.sort_by(|l, r| l.block.cmp(&r.block));
+        arms.sort_by_key(|arm| arm.block);
 
         // group by block
         let mut new_arms: Vec<MatchArm> = vec![];
@@ -332,23 +941,54 @@ fn optimize_match(match_stmt: &mut Match) {
 
 use std::collections::HashSet;
 
-pub fn compile_bytecode(bytecode: Bytecode, enum_ident: &Ident, variants: &[Ident]) -> TokenStream {
-    let mut fns: Vec<TokenStream> = vec![];
+/// Compile the bytecode into a state machine
+///
+/// Each function block is a state, so a jump into a function block sets the
+/// state and continues the dispatch loop instead of calling a function. This
+/// keeps the stack usage constant for any input.
+pub fn compile_bytecode(bytecode: Bytecode) -> TokenStream {
+    let mut dispatch = false;
+    let funcs: Vec<_> = bytecode.blocks.iter()
+        .filter(|b| b.is_func)
+        .map(|block| {
+            let mut ctx = Context { call_stack: HashSet::new(), dispatch: false };
+            let body = func_to_rust(&mut ctx, &bytecode, block.id);
+            dispatch |= ctx.dispatch;
+
+            (block.id, body)
+        })
+        .collect();
 
-    // compile only functions
-    for block in bytecode.blocks.iter().filter(|b| b.is_func) {
-        let name = format_ident!("_l{}", block.id);
+    // Without jumps between the states, l0 is the only state
+    let machine =
+        if !dispatch {
+            let body = &funcs[0].1;
 
-        let body = func_to_rust(&bytecode, block.id, enum_ident, variants);
+            quote! { #body }
+        }
+        else {
+            let states = funcs.iter()
+                .map(|(id, body)| quote! { #id => { #body } });
 
-        fns.push(quote! {
-            #[allow(clippy::needless_return)]
-            fn #name<'input>(&mut self, cursor: &mut sana::ir::Cursor<'input>) { #body }
-        });
-    }
+            quote! {
+                let mut state = 0usize;
+
+                'dispatch: loop {
+                    match state {
+                        #(#states)*
+                        _ => unreachable!(),
+                    }
+
+                    return
+                }
+            }
+        };
 
     quote! {
-        #(#fns)*
+        #[allow(unreachable_code, clippy::needless_return)]
+        fn _machine<I: sana::ir::Input + ?Sized>(&mut self, cursor: &mut sana::ir::Cursor<'_, I>) {
+            #machine
+        }
     }
 }
 
@@ -360,15 +1000,22 @@ macro_rules! format_lifetime {
     }}
 }
 
-fn func_to_rust(bytecode: &Bytecode, block_id: BlockId, enum_ident: &Ident, variants: &[Ident]) -> TokenStream {
+/// The state of the code generation for a function block
+struct Context {
+    /// The blocks being inlined
+    call_stack: HashSet<BlockId>,
+    /// Whether the code jumps into a function block
+    dispatch: bool,
+}
+
+fn func_to_rust(ctx: &mut Context, bytecode: &Bytecode, block_id: BlockId) -> TokenStream {
     let block = &bytecode.blocks[block_id];
     assert_eq!(block.id, block_id);
 
-    let mut call_stack = HashSet::new();
-    call_stack.insert(block.id);
+    ctx.call_stack.insert(block.id);
 
     let code = block.code.iter()
-        .map(|stmt| stmt_to_rust(&mut call_stack, bytecode, stmt, enum_ident, variants))
+        .map(|stmt| stmt_to_rust(ctx, bytecode, stmt))
         .collect::<Vec::<_>>();
 
     if block.is_loop {
@@ -393,33 +1040,37 @@ fn func_to_rust(bytecode: &Bytecode, block_id: BlockId, enum_ident: &Ident, vari
     }
 }
 
-fn block_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, block_id: BlockId, enum_ident: &Ident, variants: &[Ident]) -> TokenStream {
+fn block_to_rust(ctx: &mut Context, bytecode: &Bytecode, block_id: BlockId) -> TokenStream {
     let block = &bytecode.blocks[block_id];
     assert_eq!(block.id, block_id);
 
-    if block.is_loop && call_stack.contains(&block.id) {
+    if block.is_loop && ctx.call_stack.contains(&block.id) {
         // we are trying to jump into the beginning of a loop from the middle of a block
         let label = format_lifetime!("'l{}", block_id);
         return quote! { continue #label }
     }
 
     if block.is_func {
-        // any call to a function inside a block results into a call
-        let func = format_ident!("_l{}", block.id);
-        return quote! { return self.#func(cursor); }
+        // a jump into a function switches the state of the machine
+        ctx.dispatch = true;
+
+        return quote! {
+            state = #block_id;
+            continue 'dispatch;
+        }
     }
 
-    if call_stack.contains(&block.id) {
-        panic!("recursion in block {} which is not a function nor a loop. call stack: {:?}", block.id, &call_stack);
+    if ctx.call_stack.contains(&block.id) {
+        panic!("recursion in block {} which is not a function nor a loop. call stack: {:?}", block.id, &ctx.call_stack);
     }
 
-    call_stack.insert(block.id);
+    ctx.call_stack.insert(block.id);
 
     let code = block.code.iter()
-        .map(|stmt| stmt_to_rust(call_stack, bytecode, stmt, enum_ident, variants))
+        .map(|stmt| stmt_to_rust(ctx, bytecode, stmt))
         .collect::<Vec::<_>>();
 
-    call_stack.remove(&block.id);
+    ctx.call_stack.remove(&block.id);
 
     if block.is_loop {
         let label = format_lifetime!("'l{}", block.id);
@@ -443,22 +1094,21 @@ fn block_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, block_i
     }
 }
 
-fn match_arm_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, arm: &MatchArm, enum_ident: &Ident, variants: &[Ident]) -> TokenStream {
+fn match_arm_to_rust(ctx: &mut Context, bytecode: &Bytecode, arm: &MatchArm) -> TokenStream {
     let ranges = arm.ranges.iter()
         .map(|(from, to)| quote! { #from ..= #to });
-    let block = block_to_rust(call_stack, bytecode, arm.block, enum_ident, variants);
+    let block = block_to_rust(ctx, bytecode, arm.block);
 
     quote! {
         #(#ranges)|* => { #block }
     }
 }
 
-fn stmt_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, stmt: &Stmt, enum_ident: &Ident, variants: &[Ident]) -> TokenStream {
+fn stmt_to_rust(ctx: &mut Context, bytecode: &Bytecode, stmt: &Stmt) -> TokenStream {
     match stmt {
         Stmt::Set(act) => {
-            let var = &variants[*act];
             quote! {
-                self.action = Some(#enum_ident::#var);
+                self.action = Some(#act);
                 self.end = cursor.position();
             }
         },
@@ -467,7 +1117,7 @@ fn stmt_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, stmt: &S
         },
         Stmt::Match(Match { arms }) => {
             let arms = arms.iter()
-                .map(|arm| match_arm_to_rust(call_stack, bytecode, arm, enum_ident, variants))
+                .map(|arm| match_arm_to_rust(ctx, bytecode, arm))
                 .collect::<Vec::<_>>();
 
             quote! {
@@ -484,7 +1134,7 @@ fn stmt_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, stmt: &S
         },
         Stmt::JumpNotMatches { range, block } => {
             let (from, to) = range;
-            let block = block_to_rust(call_stack, bytecode, *block, enum_ident, variants);
+            let block = block_to_rust(ctx, bytecode, *block);
 
             quote! {
                 let ch = match cursor.head {
@@ -492,11 +1142,14 @@ fn stmt_to_rust(call_stack: &mut HashSet<BlockId>, bytecode: &Bytecode, stmt: &S
                     None => return,
                 };
 
-                if !(#from..=#to).contains(&ch) { #block }
+                match ch {
+                    #from..=#to => (),
+                    _ => { #block }
+                }
             }
         },
         Stmt::Jump(block_id) => {
-            block_to_rust(call_stack, bytecode, *block_id, enum_ident, variants)
+            block_to_rust(ctx, bytecode, *block_id)
         },
         Stmt::Halt =>
             quote! { return },
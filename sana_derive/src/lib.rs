@@ -3,12 +3,11 @@ use proc_macro_error::*;
 use proc_macro2::Span;
 use syn::{Ident, ItemEnum};
 
-use std::ops::Not;
-
 use sana_core::RuleSet;
 use sana_core::{Rule, regex::Regex};
+use sana_core::ir::Passes;
 
-use parser::{parse_attr, TokenAttr, RegexAttr, SanaAttr};
+use parser::{parse_attr, ModeChange, TokenAttr, RegexAttr, RuleParams, SanaAttr, SanaOption, OptimizePass};
 
 mod parser;
 mod generator;
@@ -23,28 +22,81 @@ struct Spanned<T> {
 struct SanaVariant {
     ident: Ident,
     attrs: Vec<Spanned<SanaAttr>>,
+    modes: Vec<Ident>,
+    /// Whether the variant has a field
+    data: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Backend {
     Vm,
     Rust,
+    Table,
+}
+
+/// A lexer mode with its own rule set
+#[derive(Debug, Clone)]
+struct Mode {
+    name: String,
+    rules: RuleSet<usize>,
+}
+
+/// The name of the initial lexer mode
+const DEFAULT_MODE: &str = "default";
+
+/// A variant of the token enum
+#[derive(Debug, Clone)]
+struct VariantSpec {
+    ident: Ident,
+    /// Whether a rule of the variant has the `allow_unreachable` parameter
+    allow_unreachable: bool,
+    /// The mode change performed after matching the variant
+    mode_change: Option<ModeChange<usize>>,
+    /// The parse callback of the variant
+    parse: Option<parser::Callback>,
+    /// Whether the variant has a field filled by the parse callback
+    data: bool,
+    /// Whether it is a `#[skip]` rule rather than a variant
+    ///
+    /// The ident of a skip rule is `skip`
+    skip: bool,
+    /// The patterns of the rules, separated by ` | `
+    pattern: String,
+    /// The string matched by the variant, if it has a single `#[token]` rule
+    fixed: Option<String>,
+    /// The length of the trailing context in chars
+    trailing: usize,
 }
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 struct SanaSpec {
     enum_ident: Ident,
-    rules: RuleSet<usize>,
-    variants: Vec<Ident>,
+    vis: syn::Visibility,
+    /// The lifetime of the borrowed input, if any
+    lifetime: Option<syn::Lifetime>,
+    /// The lexer modes, the first one is the initial mode
+    modes: Vec<Mode>,
+    variants: Vec<VariantSpec>,
     terminal: Ident,
     backend: Backend,
+    bytes: bool,
+    utf8: bool,
+    minimize: bool,
+    /// Whether to generate the DFA for `Lexer::next_restricted`
+    restricted: bool,
+    /// The IR optimization passes
+    passes: Passes,
+    /// The type of the user state stored in the lexer
+    extras: Option<syn::Type>,
+    /// The name of the token set alias, if it is requested
+    set: Option<Ident>,
 }
 
-fn parse_variant(var: syn::Variant) -> Option<SanaVariant> {
+fn parse_variant(var: syn::Variant, modes: Vec<Ident>, bytes: bool) -> Option<SanaVariant> {
     let ident = var.ident;
     let attrs: Vec<_> = var.attrs.into_iter()
-        .filter_map(parse_attr)
+        .filter_map(|attr| parse_attr(attr, bytes))
         .collect();
 
     if attrs.is_empty() {
@@ -72,23 +124,84 @@ fn parse_variant(var: syn::Variant) -> Option<SanaVariant> {
         }
     }
 
-    if var.fields.is_empty().not() {
-        emit_error!(var.fields, "Enum variants with fields are not supported");
-        return None
-    }
+    let data = match &var.fields {
+        syn::Fields::Unit => false,
+        syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => true,
+        fields => {
+            emit_error!(
+                fields, "Unsupported variant fields";
+                note = "A variant may only have a single unnamed field, such as `Integer(i64)`"
+            );
+            return None
+        },
+    };
 
-    Some(SanaVariant { ident, attrs })
+    Some(SanaVariant { ident, attrs, modes, data })
 }
 
-fn join_attrs<T>(attrs: &[Spanned<SanaAttr>], action: T) -> Rule<T> {
-    let (regex, priority) = match &attrs[0].data {
-        SanaAttr::Regex(RegexAttr { regex, priority }) =>
-            (regex.clone(), *priority),
-        SanaAttr::Token(TokenAttr { token, priority }) =>
-            (token.clone(), *priority),
+fn rule_attr(attr: &Spanned<SanaAttr>) -> (Regex, RuleParams) {
+    match &attr.data {
+        SanaAttr::Regex(RegexAttr { regex, params, .. }) =>
+            (regex.clone(), params.clone()),
+        SanaAttr::Token(TokenAttr { token, params, .. }) =>
+            (token.clone(), params.clone()),
         _ => unreachable!()
+    }
+}
+
+/// The pattern of the variant and its fixed string, if any
+fn variant_pattern(attrs: &[Spanned<SanaAttr>]) -> (String, Option<String>) {
+    let patterns: Vec<_> = attrs.iter()
+        .filter_map(|attr| match &attr.data {
+            SanaAttr::Regex(regex) => Some((&regex.pattern, &regex.params)),
+            SanaAttr::Token(token) => Some((&token.pattern, &token.params)),
+            SanaAttr::Error => None,
+        })
+        .map(|(pattern, params)| match &params.followed_by {
+            Some(trailing) => format!("{}/{}", pattern, trailing.pattern),
+            None => pattern.clone(),
+        })
+        .collect();
+
+    let fixed = match attrs {
+        [Spanned { data: SanaAttr::Token(token), .. }] => token.string.clone(),
+        _ => None,
+    };
+
+    (patterns.join(" | "), fixed)
+}
+
+/// Add the trailing context of the parameters to the rule
+fn followed_by<T>(rule: Rule<T>, params: &RuleParams) -> Rule<T> {
+    match &params.followed_by {
+        Some(trailing) => rule.followed_by(trailing.regex.clone()),
+        None => rule,
+    }
+}
+
+/// The length of the trailing context of the rule, zero if there is none
+fn trailing_len(params: &RuleParams, span: Span) -> usize {
+    let trailing = match &params.followed_by {
+        Some(trailing) => &trailing.regex,
+        None => return 0,
     };
 
+    match trailing.fixed_length() {
+        Some(len) => len,
+        None => {
+            emit_error!(
+                span, "Trailing context of variable length";
+                note = "The `followed_by` expression must match strings of the same length"
+            );
+
+            0
+        },
+    }
+}
+
+fn join_attrs<T>(attrs: &[Spanned<SanaAttr>], action: T) -> (Rule<T>, RuleParams) {
+    let (regex, params) = rule_attr(&attrs[0]);
+
     if regex.is_nullable() {
         emit_error!(
             attrs[0].span, "Nullable regular expression";
@@ -98,13 +211,7 @@ fn join_attrs<T>(attrs: &[Spanned<SanaAttr>], action: T) -> Rule<T> {
 
     let mut union = vec![];
     for attr in &attrs[1..] {
-        let (regex, prio) = match &attr.data {
-            SanaAttr::Regex(RegexAttr { regex, priority }) =>
-                (regex.clone(), *priority),
-            SanaAttr::Token(TokenAttr { token, priority }) =>
-                (token.clone(), *priority),
-            _ => unreachable!()
-        };
+        let (regex, attr_params) = rule_attr(attr);
 
         if regex.is_nullable() {
             emit_error!(
@@ -113,10 +220,32 @@ fn join_attrs<T>(attrs: &[Spanned<SanaAttr>], action: T) -> Rule<T> {
             );
         }
 
-        if priority != prio {
+        if params.priority != attr_params.priority {
             emit_error!(
                 attr.span, "Conflicting rule precedences";
-                note = "The precedence of the first rule is equal to {}", priority
+                note = "The precedence of the first rule is equal to {}", params.priority
+            );
+        }
+
+        if params.mode_change != attr_params.mode_change {
+            emit_error!(
+                attr.span, "Conflicting mode changes";
+                note = "All rules of a variant must change the mode in the same way"
+            );
+        }
+
+        if params.parse != attr_params.parse {
+            emit_error!(
+                attr.span, "Conflicting parse callbacks";
+                note = "All rules of a variant must have the same parse callback"
+            );
+        }
+
+        let trailing = |params: &RuleParams| params.followed_by.as_ref().map(|t| t.regex.clone());
+        if trailing(&params) != trailing(&attr_params) {
+            emit_error!(
+                attr.span, "Conflicting trailing contexts";
+                note = "All rules of a variant must have the same `followed_by` parameter"
             );
         }
 
@@ -127,37 +256,150 @@ fn join_attrs<T>(attrs: &[Spanned<SanaAttr>], action: T) -> Rule<T> {
         if union.is_empty() { regex }
         else { Regex::Or(Some(regex).into_iter().chain(union).collect()) };
 
-    Rule { regex, priority, action }
+    let rule = followed_by(Rule { regex, priority: params.priority, action }, &params);
+
+    (rule, params)
 }
 
-fn build_spec(source: ItemEnum) -> SanaSpec {
-    if source.generics.lt_token.is_some() {
-        abort!(source.generics, "Generics are not supported")
+/// Find the index of the mode with the given name
+fn resolve_mode(modes: &[Mode], name: &Ident) -> usize {
+    match modes.iter().position(|m| *name == m.name) {
+        Some(ix) => ix,
+        None => {
+            emit_error!(
+                name, "Unknown mode `{}`", name;
+                note = "A mode must contain at least one variant marked with #[mode({})]", name
+            );
+
+            0
+        },
+    }
+}
+
+/// Get the lifetime of the enum, which is the only supported generic parameter
+fn enum_lifetime(generics: &syn::Generics) -> Option<syn::Lifetime> {
+    let params: Vec<_> = generics.params.iter().collect();
+
+    match params[..] {
+        [] => None,
+        [syn::GenericParam::Lifetime(def)] if def.bounds.is_empty() && generics.where_clause.is_none() =>
+            Some(def.lifetime.clone()),
+        _ => abort!(
+            generics, "Generics are not supported";
+            note = "The enum may only have a single lifetime parameter, such as `Token<'s>`"
+        ),
     }
+}
+
+fn build_spec(source: ItemEnum) -> SanaSpec {
+    let lifetime = enum_lifetime(&source.generics);
 
     let mut backend = Backend::Rust;
+    let mut bytes = None;
+    let mut utf8 = None;
+    let mut minimize = true;
+    let mut restricted = false;
+    let mut passes = Passes::ALL;
+    let mut extras = None;
+    let mut set = None;
+    let mut skips = vec![];
     for attr in source.attrs {
-        if let Some(b) = parser::parse_backend_attr(attr) {
+        if let Some(options) = parser::parse_sana_attr(attr.clone()) {
+            for opt in options {
+                match opt.data {
+                    SanaOption::Bytes => bytes = Some(opt.span),
+                    SanaOption::Utf8 => utf8 = Some(opt.span),
+                    SanaOption::NoMinimize => minimize = false,
+                    SanaOption::Restricted => restricted = true,
+                    SanaOption::NoOptimize(disabled) if disabled.is_empty() =>
+                        passes = Passes::NONE,
+                    SanaOption::NoOptimize(disabled) => for pass in disabled {
+                        match pass {
+                            OptimizePass::ThreadJumps => passes.thread_jumps = false,
+                            OptimizePass::DedupBlocks => passes.dedup_blocks = false,
+                            OptimizePass::CoalesceRanges => passes.coalesce_ranges = false,
+                            OptimizePass::RemoveDeadBlocks => passes.remove_dead_blocks = false,
+                        }
+                    },
+                    SanaOption::Extras(ty) => extras = Some(*ty),
+                    SanaOption::Set(ident) => set = Some(ident),
+                }
+            }
+        }
+        else if attr.path.is_ident("skip") {
+            skips.push(attr)
+        }
+        else if let Some(b) = parser::parse_backend_attr(attr) {
             backend = b
         }
     }
 
+    if let (Some(_), Some(span)) = (bytes, utf8) {
+        emit_error!(
+            span, "The utf8 option is not compatible with bytes";
+            note = "The DFA of a bytes lexer already works on bytes"
+        );
+    }
+    let (bytes, utf8) = (bytes.is_some(), utf8.is_some());
+
     let enum_ident = source.ident;
-    let mut rules = vec![];
+    let vis = source.vis;
     let mut variants = vec![];
     let mut terminal = None;
 
+    let var_modes: Vec<Vec<Ident>> = source.variants.iter()
+        .map(|var| var.attrs.iter()
+            .filter_map(parser::parse_mode_attr)
+            .flatten()
+            .collect())
+        .collect();
+
+    // The modes are collected first, so the mode changes can refer to
+    // the modes of the later variants
+    let mut modes = vec![Mode { name: DEFAULT_MODE.into(), rules: RuleSet::new() }];
+    let names = source.variants.iter()
+        .zip(&var_modes)
+        .filter(|(var, _)| !var.attrs.iter().any(|attr| attr.path.is_ident("error")))
+        .flat_map(|(_, names)| names);
+    for name in names {
+        if modes.iter().all(|m| *name != m.name) {
+            modes.push(Mode { name: name.to_string(), rules: RuleSet::new() })
+        }
+    }
+
     let vars = source.variants.into_iter()
-        .filter_map(parse_variant);
+        .zip(var_modes)
+        .filter_map(|(var, modes)| parse_variant(var, modes, bytes));
     for (i, var) in vars.enumerate() {
         if var.attrs.iter().any(|a| a.data == SanaAttr::Error) {
+            if let Some(mode) = var.modes.first() {
+                emit_error!(
+                    mode, "#[mode] on the #[error] variant";
+                    note = "The error variant is shared by all modes"
+                );
+            }
+
+            if var.data {
+                emit_error!(var.ident, "The #[error] variant must not have fields");
+            }
+
             if terminal.is_some() {
                 emit_error!(var.ident, "More than one #[error] token");
 
                 continue
             }
             else {
-                variants.push(var.ident.clone());
+                variants.push(VariantSpec {
+                    ident: var.ident.clone(),
+                    allow_unreachable: false,
+                    mode_change: None,
+                    parse: None,
+                    data: false,
+                    skip: false,
+                    pattern: String::new(),
+                    fixed: None,
+                    trailing: 0,
+                });
                 terminal = Some(var.ident);
 
                 continue
@@ -168,8 +410,110 @@ fn build_spec(source: ItemEnum) -> SanaSpec {
             .filter(|a| a.data != SanaAttr::Error)
             .collect();
 
-        rules.push(join_attrs(&attrs, i));
-        variants.push(var.ident)
+        let (rule, params) = join_attrs(&attrs, i);
+        let (pattern, fixed) = variant_pattern(&attrs);
+        let trailing = trailing_len(&params, attrs[0].span);
+        let allow_unreachable = attrs.iter().any(|attr| match &attr.data {
+            SanaAttr::Regex(regex) => regex.params.allow_unreachable,
+            SanaAttr::Token(token) => token.params.allow_unreachable,
+            SanaAttr::Error => false,
+        });
+
+        if let Some(mode) = params.modes.first() {
+            emit_error!(
+                mode, "The mode parameter is only allowed in #[skip]";
+                help = "Use #[mode({})] on the variant instead", mode
+            );
+        }
+
+        if var.data && params.parse.is_none() {
+            emit_error!(
+                var.ident, "Variants with fields require a parse callback";
+                help = "Set the callback with `parse = <function>` or `callback = <function>`"
+            );
+        }
+
+        let var_modes =
+            if var.modes.is_empty() { vec![DEFAULT_MODE.to_string()] }
+            else { var.modes.iter().map(|m| m.to_string()).collect() };
+        for name in var_modes {
+            if let Some(mode) = modes.iter_mut().find(|m| m.name == name) {
+                mode.rules.rules.push(rule.clone())
+            }
+        }
+
+        let mode_change = params.mode_change.map(|change| match change {
+            ModeChange::Push(name) => ModeChange::Push(resolve_mode(&modes, &name)),
+            ModeChange::Switch(name) => ModeChange::Switch(resolve_mode(&modes, &name)),
+            ModeChange::Pop => ModeChange::Pop,
+        });
+
+        variants.push(VariantSpec {
+            ident: var.ident,
+            allow_unreachable,
+            mode_change,
+            parse: params.parse,
+            data: var.data,
+            skip: false,
+            pattern,
+            fixed,
+            trailing,
+        });
+    }
+
+    for attr in skips {
+        let skip = match parser::parse_skip_attr(&attr, bytes) {
+            Some(skip) => skip,
+            None => continue,
+        };
+        let params = &skip.data.params;
+
+        if skip.data.regex.is_nullable() {
+            emit_error!(
+                skip.span, "Nullable regular expression";
+                note = "The regular expression should not match the empty string, but it does"
+            );
+        }
+
+        if params.mode_change.is_some() || params.parse.is_some() {
+            emit_error!(
+                skip.span, "Invalid #[skip] parameter";
+                note = "Skip rules can not change the mode or have a parse callback"
+            );
+        }
+
+        let rule = Rule {
+            regex: skip.data.regex.clone(),
+            priority: params.priority,
+            action: variants.len(),
+        };
+        let rule = followed_by(rule, params);
+        let trailing = trailing_len(params, skip.span);
+
+        let skip_modes =
+            if params.modes.is_empty() { vec![DEFAULT_MODE.to_string()] }
+            else { params.modes.iter().map(|m| m.to_string()).collect() };
+        for name in skip_modes {
+            match modes.iter_mut().find(|m| m.name == name) {
+                Some(mode) => mode.rules.rules.push(rule.clone()),
+                None => emit_error!(
+                    skip.span, "Unknown mode `{}`", name;
+                    note = "A mode must contain at least one variant marked with #[mode({})]", name
+                ),
+            }
+        }
+
+        variants.push(VariantSpec {
+            ident: Ident::new("skip", skip.span),
+            allow_unreachable: params.allow_unreachable,
+            mode_change: None,
+            parse: None,
+            data: false,
+            skip: true,
+            pattern: skip.data.pattern.clone(),
+            fixed: None,
+            trailing,
+        });
     }
 
     if terminal.is_none() {
@@ -178,10 +522,19 @@ fn build_spec(source: ItemEnum) -> SanaSpec {
 
     SanaSpec {
         enum_ident,
-        rules: RuleSet { rules },
+        vis,
+        lifetime,
+        modes,
         variants,
         terminal: terminal.unwrap(),
-        backend
+        backend,
+        bytes,
+        utf8,
+        minimize,
+        restricted,
+        passes,
+        extras,
+        set,
     }
 }
 
@@ -189,17 +542,163 @@ fn build_spec(source: ItemEnum) -> SanaSpec {
 ///
 /// # Attributes
 ///
-/// - `#[backend(be)]`: set the lexer backend. Valid values are `rust` and `vm`.
-/// This attribute must be placed before the enum definiton.
+/// - `#[backend(be)]`: set the lexer backend. Valid values are `rust`, `vm`
+/// and `table`. The `rust` backend compiles the DFA into a state machine in
+/// Rust code, its stack usage does not depend on the length of tokens. The
+/// `vm` backend executes the IR of the DFA. The `table` backend emits a
+/// static transition table over the equivalence classes of chars, which
+/// compiles faster and is smaller than the code of the `rust` backend for
+/// large grammars. This attribute must be placed before the enum definiton.
+/// - `#[sana(options)]`: set lexer options. This attribute must be placed before
+/// the enum definition. The options are:
+///     - `bytes`: lex `&[u8]` instead of `&str`. Regular expressions match bytes,
+///     so `(?-u)` classes and byte escapes such as `(?-u:\xFF)` are allowed, and
+///     Unicode characters match their UTF-8 encoding. `#[token]` also accepts
+///     byte strings.
+///     - `utf8`: compile the DFA to transitions on UTF-8 bytes. The lexer still
+///     scans `&str`, but does not decode chars while matching.
+///     - `no_minimize`: do not minimize the DFA. By default the equivalent
+///     states of the DFA are merged before generating the code.
+///     - `restricted`: generate the DFAs for `Lexer::next_restricted`. They
+///     are built separately from the DFAs of the lexer, so the option makes
+///     the compilation slower and the binary larger.
+///     - `no_optimize`: do not optimize the IR of the `rust` and `vm` backends.
+///     `no_optimize(pass, ...)` disables only the given passes, which are
+///     `thread_jumps`, `dedup_blocks`, `coalesce_ranges` and
+///     `remove_dead_blocks`. This is useful to debug the generated code.
+///     - `extras = <type>`: the type of the user state stored in the lexer,
+///     see `Lexer::extras`. It is passed to the `callback` functions. The type
+///     must implement `Default`. The default is `()`.
+///     - `set = <name>`: generate a type alias with the given name for the
+///     set of the variants, see the token sets below.
 /// - `#[error]`: mark the given variant as the error variant. There must be
 /// exactly one error variant for a given enum
 /// - `#[regex(re)]`: specify the regular expression corresponding to
 /// the given variant
 /// - `#[token(tok)]`: specify the string corresponding to the given variant
+/// - `#[mode(m1, m2, ...)]`: put the variant into the given lexer modes.
+/// Variants without this attribute belong to the `default` mode
+/// - `#[skip(re)]`: skip the input matched by the regular expression. This
+/// attribute must be placed before the enum definition and can be repeated.
+/// The skipped input never reaches the iterator and has no variant. Besides
+/// `priority`, it accepts `mode = <mode>` parameters, which set the modes of
+/// the rule (the default is the `default` mode)
+///
+/// A variant that can never be produced, because its rule does not match any
+/// string or other rules are always chosen instead, is an error. Add the
+/// `allow_unreachable` parameter to its rule to allow it.
 ///
 /// Attributes `regex` and `token` can also receive the following parameters:
 ///
 /// - `priority = <integer>` (default is `0`): the priority for the rule
+/// - `push = <mode>`: push the mode onto the mode stack after the rule is matched
+/// - `pop`: pop the current mode from the mode stack after the rule is matched
+/// - `switch = <mode>`: replace the current mode after the rule is matched
+/// - `parse = <function>`: construct the token from the matched slice with
+/// the given function. The function takes the slice and returns either
+/// `Option<T>` or `Result<T, E>`. For a variant with a field, such as
+/// `Integer(i64)`, `T` is the type of the field, and for a unit variant, it is
+/// `()`. If the function fails, the error token is produced instead
+/// - `callback = <function>`: same as `parse`, but the function also takes
+/// `&mut` extras of the lexer after the slice
+/// - `followed_by = <regex>`: the trailing context of the rule, as in the
+/// `r1/r2` rules of flex. See below
+/// - `allow_unreachable`: do not report an error if the variant can never be
+/// produced, see above
+///
+/// # Trailing context
+///
+/// A rule with `followed_by` matches only if the match is followed by a
+/// string matched by the trailing context, which is not included in the
+/// token. The trailing context uses the same syntax as `regex`, and it must
+/// match strings of the same length, such as a single char:
+///
+/// ```ignore
+/// #[derive(Debug, Clone, Copy, PartialEq, Sana)]
+/// enum Token {
+///     #[regex("[a-z]+")]
+///     Ident,
+///     // An identifier right before a colon
+///     #[regex("[a-z]+", followed_by = ":")]
+///     Label,
+///     #[token(":")]
+///     Colon,
+///     #[error]
+///     Error,
+/// }
+/// ```
+///
+/// Like other rules, it is chosen by the longest match, which includes the
+/// trailing context. The trailing context must be present, so the rule does
+/// not match at the end of input.
+///
+/// # Variants with data
+///
+/// A variant may have a single unnamed field filled by its parse callback.
+/// The enum may also have a single lifetime parameter, which is the lifetime
+/// of the input, so the variants can borrow from it:
+///
+/// ```ignore
+/// #[derive(Debug, Clone, Sana)]
+/// enum Token<'s> {
+///     #[regex("[a-z]+", parse = ident)]
+///     Ident(&'s str),
+///     #[regex("[0-9]+", parse = str::parse)]
+///     Integer(i64),
+///     #[error]
+///     Error,
+/// }
+///
+/// fn ident(s: &str) -> Option<&str> { Some(s) }
+/// ```
+///
+/// # Lexer modes
+///
+/// Each mode has its own DFA, built from the rules of the variants in that
+/// mode. The lexer starts in the `default` mode and keeps a stack of modes.
+/// The `#[error]` variant is shared by all modes.
+///
+/// # Token sets
+///
+/// A `sana::TokenSet` over the variants of the enum is named
+/// `<Token as Sana>::Set`. The derive does not add items next to the enum,
+/// unless a type alias is requested with `#[sana(set = Name)]`. `Lexer::next_restricted` takes such
+/// a set and matches only the variants in it. It requires the `restricted`
+/// option, with which each mode gets a table-driven DFA, whose states hold
+/// all variants that match in them.
+///
+/// The variants are numbered in the order of declaration. The enum gets the
+/// `COUNT` constant, the `const fn index(&self)` method and the
+/// `const fn from_index(usize)` function, which returns `None` for the
+/// variants with a field. The sets can be built in constant expressions:
+///
+/// ```ignore
+/// const FIRST_EXPR: <Token as Sana>::Set = <Token as Sana>::Set::new()
+///     .with(Token::LParen.index())
+///     .with(Token::Ident.index());
+///
+/// assert!(FIRST_EXPR.contains(&Token::Ident));
+/// assert_eq!(Token::from_index(Token::RParen.index()), Some(Token::RParen));
+/// ```
+///
+/// # Variant metadata
+///
+/// The enum also gets methods describing the variants, which are useful for
+/// error messages and pretty-printing:
+///
+/// - `name()` is the name of the variant, such as `"LParen"`
+/// - `pattern()` is the regular expression or the token string of the
+///   variant, as written in the attribute. The patterns of several rules are
+///   separated by ` | `, and the pattern of the `#[error]` variant is empty
+/// - `fixed_str()` is the string matched by a variant with a single
+///   `#[token]` rule, and `None` for the other variants
+/// - `ALL` is the array of all unit variants in the order of declaration
+///
+/// ```ignore
+/// assert_eq!(Token::LParen.fixed_str(), Some("("));
+/// assert_eq!(Token::Ident.pattern(), "[a-z]+");
+/// assert_eq!(Token::ALL[1].name(), "RParen");
+/// ```
 ///
 /// # Regular expression syntax
 ///
@@ -223,7 +722,7 @@ fn build_spec(source: ItemEnum) -> SanaSpec {
 /// The priorities of the operations match the order in the syntax definiton.
 /// So `a | b . c` is the same as `a | (b . c)`.
 #[proc_macro_error]
-#[proc_macro_derive(Sana, attributes(backend, error, regex, token))]
+#[proc_macro_derive(Sana, attributes(backend, error, mode, regex, sana, skip, token))]
 pub fn sana(input: TokenStream) -> TokenStream {
     let item: ItemEnum = syn::parse(input)
         .expect_or_abort("Sana can be only be derived for enums");
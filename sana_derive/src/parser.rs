@@ -1,14 +1,14 @@
 use proc_macro_error::emit_error;
-use syn::{parenthesized, Attribute, Ident, LitInt, Token, LitStr};
-use syn::parse::{Parse, ParseStream, Peek};
+use syn::{parenthesized, Attribute, Ident, LitInt, Token, LitStr, LitByteStr};
+use syn::buffer::Cursor;
+use syn::parse::{Parse, ParseStream, Parser, Peek};
+use proc_macro2::TokenStream;
 
 use std::convert::TryFrom;
 
 use sana_core::regex::Regex;
 use crate::Spanned;
 
-struct RegexExpr(Regex);
-
 pub(crate) fn parse_backend_attr(attr: Attribute) -> Option<crate::Backend> {
     let name = attr.path.get_ident()?.to_string();
     if &*name != "backend" { return None }
@@ -32,11 +32,116 @@ impl Parse for BackendAttr {
         match &*backend.to_string() {
             "vm" => Ok(BackendAttr(crate::Backend::Vm)),
             "rust" => Ok(BackendAttr(crate::Backend::Rust)),
+            "table" => Ok(BackendAttr(crate::Backend::Table)),
             _ => Err(input.error("Invalid backend"))
         }
     }
 }
 
+/// An option of the `#[sana(...)]` attribute
+#[derive(Debug, Clone, PartialEq)]
+pub enum SanaOption {
+    /// Scan `[u8]` instead of `str`
+    Bytes,
+    /// Compile the DFA to UTF-8 byte transitions
+    Utf8,
+    /// Do not minimize the DFA
+    NoMinimize,
+    /// Generate the DFA for `Lexer::next_restricted`
+    Restricted,
+    /// Do not run the given IR optimization passes, or all passes if none
+    /// are given
+    NoOptimize(Vec<OptimizePass>),
+    /// The type of the user state stored in the lexer
+    Extras(Box<syn::Type>),
+    /// The name of the token set alias
+    Set(Ident),
+}
+
+/// An IR optimization pass, named in `no_optimize(...)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptimizePass {
+    ThreadJumps,
+    DedupBlocks,
+    CoalesceRanges,
+    RemoveDeadBlocks,
+}
+
+impl Parse for OptimizePass {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+
+        match &*ident.to_string() {
+            "thread_jumps" => Ok(OptimizePass::ThreadJumps),
+            "dedup_blocks" => Ok(OptimizePass::DedupBlocks),
+            "coalesce_ranges" => Ok(OptimizePass::CoalesceRanges),
+            "remove_dead_blocks" => Ok(OptimizePass::RemoveDeadBlocks),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "Unknown optimization pass"
+            )),
+        }
+    }
+}
+
+pub(crate) fn parse_sana_attr(attr: Attribute) -> Option<Vec<Spanned<SanaOption>>> {
+    let name = attr.path.get_ident()?.to_string();
+    if &*name != "sana" { return None }
+
+    parse_sana_options.parse2(attr.tokens)
+        .map_err(|e| emit_error!(e))
+        .ok()
+}
+
+fn parse_sana_options(input: ParseStream) -> syn::Result<Vec<Spanned<SanaOption>>> {
+    let content;
+    parenthesized!(content in input);
+
+    let options = content.parse_terminated::<_, Token![,]>(parse_sana_option)?;
+
+    Ok(options.into_iter().collect())
+}
+
+fn parse_sana_option(input: ParseStream) -> syn::Result<Spanned<SanaOption>> {
+    let ident: Ident = input.parse()?;
+
+    let data = match &*ident.to_string() {
+        "bytes" => SanaOption::Bytes,
+        "utf8" => SanaOption::Utf8,
+        "no_minimize" => SanaOption::NoMinimize,
+        "restricted" => SanaOption::Restricted,
+        "no_optimize" => {
+            if !input.peek(syn::token::Paren) {
+                SanaOption::NoOptimize(vec![])
+            }
+            else {
+                let content;
+                parenthesized!(content in input);
+
+                let passes = content.parse_terminated::<_, Token![,]>(OptimizePass::parse)?;
+
+                SanaOption::NoOptimize(passes.into_iter().collect())
+            }
+        },
+        "extras" => {
+            input.parse::<Token![=]>()?;
+
+            SanaOption::Extras(Box::new(input.parse()?))
+        },
+        "set" => {
+            input.parse::<Token![=]>()?;
+
+            SanaOption::Set(input.parse()?)
+        },
+        _ => return Err(syn::Error::new(
+            ident.span(),
+            "Invalid option"
+        ))
+    };
+
+    Ok(Spanned { data, span: ident.span() })
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum SanaAttr {
     Regex(RegexAttr),
@@ -44,16 +149,18 @@ pub enum SanaAttr {
     Error,
 }
 
-pub(crate) fn parse_attr(attr: Attribute) -> Option<Spanned<SanaAttr>> {
+pub(crate) fn parse_attr(attr: Attribute, bytes: bool) -> Option<Spanned<SanaAttr>> {
     let name = attr.path.get_ident()?.to_string();
     let data = match &*name {
         "regex" => SanaAttr::Regex(
-            syn::parse2(attr.tokens)
+            (|input: ParseStream| RegexAttr::parse(input, bytes))
+                .parse2(attr.tokens)
                 .map_err(|e| emit_error!(e))
                 .ok()?
         ),
         "token" => SanaAttr::Token(
-            syn::parse2(attr.tokens)
+            (|input: ParseStream| TokenAttr::parse(input, bytes))
+                .parse2(attr.tokens)
                 .map_err(|e| emit_error!(e))
                 .ok()?
         ),
@@ -70,22 +177,23 @@ pub(crate) fn parse_attr(attr: Attribute) -> Option<Spanned<SanaAttr>> {
 
 fn parse_infix<Any, T, Op, F>(
     input: ParseStream,
+    bytes: bool,
     op: Op,
     cons: F,
-    higher: fn(ParseStream) -> syn::Result<Regex>
+    higher: fn(ParseStream, bool) -> syn::Result<Regex>
 ) -> syn::Result<Regex>
 where
     T: Parse,
     Op: Copy + Peek + FnOnce(Any) -> T,
     F: FnOnce(Vec<Regex>) -> Regex,
 {
-    let head = input.call(higher)?;
+    let head = higher(input, bytes)?;
 
     let mut tail = vec![];
     while input.peek(op) {
         input.parse::<T>()?;
 
-        let expr = input.call(higher)?;
+        let expr = higher(input, bytes)?;
         tail.push(expr);
     }
 
@@ -97,191 +205,373 @@ where
     }
 }
 
-fn parse_regex_or(input: ParseStream) -> syn::Result<Regex> {
+fn parse_regex_or(input: ParseStream, bytes: bool) -> syn::Result<Regex> {
     parse_infix(
         input,
+        bytes,
         Token![|],
         Regex::Or,
         parse_regex_and
     )
 }
 
-fn parse_regex_and(input: ParseStream) -> syn::Result<Regex> {
+fn parse_regex_and(input: ParseStream, bytes: bool) -> syn::Result<Regex> {
     parse_infix(
         input,
+        bytes,
         Token![&],
         Regex::And,
         parse_regex_dot
     )
 }
 
-fn parse_regex_dot(input: ParseStream) -> syn::Result<Regex> {
+fn parse_regex_dot(input: ParseStream, bytes: bool) -> syn::Result<Regex> {
     parse_infix(
         input,
+        bytes,
         Token![.],
         Regex::Concat,
         parse_regex_not
     )
 }
 
-fn parse_regex_not(input: ParseStream) -> syn::Result<Regex> {
+fn parse_regex_not(input: ParseStream, bytes: bool) -> syn::Result<Regex> {
     if input.peek(Token![!]) {
         input.parse::<Token![!]>()?;
-        let inner = parse_regex_not(input)?;
+        let inner = parse_regex_not(input, bytes)?;
 
         Ok(Regex::Not(Box::new(inner)))
     }
     else {
-        parse_regex_other(input)
+        parse_regex_other(input, bytes)
     }
 }
 
-fn parse_regex_other(input: ParseStream) -> syn::Result<Regex> {
+fn parse_regex_other(input: ParseStream, bytes: bool) -> syn::Result<Regex> {
     if input.peek(syn::LitStr) {
         let regex: LitStr = input.parse()?;
         let span = regex.span();
-        let hir = regex_syntax::Parser::new()
+        let hir = regex_syntax::ParserBuilder::new()
+                .allow_invalid_utf8(bytes)
+                .build()
                 .parse(&regex.value())
                 .map_err(|e| syn::Error::new(span, e))?;
-        let regex = Regex::try_from(hir)
-            .map_err(|e| syn::Error::new(span, e))?;
+        let regex =
+            if bytes { Regex::try_from_bytes(hir) }
+            else { Regex::try_from(hir) };
 
-        Ok(regex)
+        regex.map_err(|e| syn::Error::new(span, e))
     }
     else {
         let content;
         parenthesized!(content in input);
 
-        parse_regex_or(&content)
+        parse_regex_or(&content, bytes)
     }
 }
 
-impl Parse for RegexExpr {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        if input.is_empty() || input.peek(Token![,]) {
-            return Err(input.error("Empty regex"))
-        }
-
-        let regex = parse_regex_or(input)?;
-
-        Ok(RegexExpr(regex))
+fn parse_regex_expr(input: ParseStream, bytes: bool) -> syn::Result<Regex> {
+    if input.is_empty() || input.peek(Token![,]) {
+        return Err(input.error("Empty regex"))
     }
+
+    parse_regex_or(input, bytes)
 }
 
 struct KeyValue {
     key: Ident,
-    value: Value
+    value: Option<Value>,
 }
 
 enum Value {
-    Int(LitInt)
+    Int(LitInt),
+    Path(syn::Path),
+    Regex(Trailing),
 }
 
-impl Parse for KeyValue {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let key = input.parse()?;
+impl KeyValue {
+    fn parse(input: ParseStream, bytes: bool) -> syn::Result<Self> {
+        let key: Ident = input.parse()?;
+
+        if !input.peek(Token![=]) {
+            return Ok(KeyValue { key, value: None })
+        }
 
         input.parse::<Token![=]>()?;
 
         let value =
-            if input.peek(LitInt) {
+            if key == "followed_by" {
+                let begin = input.cursor();
+                let regex = parse_regex_expr(input, bytes)?;
+                let pattern = regex_pattern(begin, input.cursor());
+
+                Value::Regex(Trailing { regex, pattern })
+            }
+            else if input.peek(LitInt) {
                 Value::Int(input.parse()?)
             }
+            else if input.peek(Ident) || input.peek(Token![::]) {
+                Value::Path(input.parse()?)
+            }
             else {
                 return Err(input.error("Invalid value"))
             };
 
-        Ok(KeyValue { key, value })
+        Ok(KeyValue { key, value: Some(value) })
     }
 }
 
+/// A change of the lexer mode after the token is matched
 #[derive(Debug, Clone, PartialEq)]
-pub struct RegexAttr {
-    pub regex: Regex,
-    pub priority: usize,
+pub enum ModeChange<M = Ident> {
+    /// Push the mode onto the mode stack
+    Push(M),
+    /// Pop the current mode from the mode stack
+    Pop,
+    /// Replace the current mode
+    Switch(M),
 }
 
+/// A callback that constructs the token from the matched slice
 #[derive(Debug, Clone, PartialEq)]
-pub struct TokenAttr {
-    pub token: Regex,
-    pub priority: usize,
+pub struct Callback {
+    pub path: syn::Path,
+    /// Whether the callback also takes the extras of the lexer
+    pub extras: bool,
 }
 
-impl Parse for RegexAttr {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let content;
-        parenthesized!(content in input);
-        let input = content;
+/// The trailing context of a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trailing {
+    pub regex: Regex,
+    /// The regular expression as written in the attribute
+    pub pattern: String,
+}
 
-        let regex = input.parse::<RegexExpr>()?.0;
+/// The parameters of `#[regex]` and `#[token]` attributes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RuleParams {
+    pub priority: usize,
+    pub mode_change: Option<ModeChange>,
+    /// The callback that constructs the token from the matched slice
+    pub parse: Option<Callback>,
+    /// The modes of a `#[skip]` rule
+    pub modes: Vec<Ident>,
+    /// The trailing context, which must follow the match
+    pub followed_by: Option<Trailing>,
+    /// Whether the rule may be unreachable
+    pub allow_unreachable: bool,
+}
+
+impl RuleParams {
+    /// Parse the parameters after the regex or the token
+    fn parse(input: ParseStream, bytes: bool) -> syn::Result<Self> {
+        let mut params = RuleParams::default();
 
         if input.is_empty() {
-            return Ok(RegexAttr {
-                regex,
-                priority: 0,
-            })
+            return Ok(params)
         }
         else {
             input.parse::<Token![,]>()?;
         }
 
-        let mut priority = 0;
+        let mut kvs = vec![];
+        while !input.is_empty() {
+            kvs.push(KeyValue::parse(input, bytes)?);
+
+            if input.is_empty() { break }
+            input.parse::<Token![,]>()?;
+        }
 
-        let kvs = input.parse_terminated::<_, Token![,]>(KeyValue::parse)?;
         for kv in kvs {
-            match &*kv.key.to_string() {
-                "priority" => {
-                    let Value::Int(value) = kv.value;
+            let key = kv.key.to_string();
+            let mode_change = match (&*key, kv.value) {
+                ("priority", Some(Value::Int(value))) => {
+                    params.priority = value.base10_parse()?;
 
-                    priority = value.base10_parse()?;
+                    continue
+                },
+                ("parse", Some(Value::Path(path))) | ("callback", Some(Value::Path(path))) => {
+                    if params.parse.is_some() {
+                        return Err(syn::Error::new(
+                            kv.key.span(),
+                            "More than one callback"
+                        ))
+                    }
+                    params.parse = Some(Callback { path, extras: key == "callback" });
+
+                    continue
+                },
+                ("followed_by", Some(Value::Regex(trailing))) => {
+                    if params.followed_by.is_some() {
+                        return Err(syn::Error::new(
+                            kv.key.span(),
+                            "More than one trailing context"
+                        ))
+                    }
+                    params.followed_by = Some(trailing);
+
+                    continue
                 },
+                ("allow_unreachable", None) => {
+                    params.allow_unreachable = true;
+
+                    continue
+                },
+                ("mode", Some(Value::Path(mode))) if mode.get_ident().is_some() => {
+                    params.modes.push(mode.get_ident().unwrap().clone());
+
+                    continue
+                },
+                ("push", Some(Value::Path(mode))) if mode.get_ident().is_some() =>
+                    ModeChange::Push(mode.get_ident().unwrap().clone()),
+                ("switch", Some(Value::Path(mode))) if mode.get_ident().is_some() =>
+                    ModeChange::Switch(mode.get_ident().unwrap().clone()),
+                ("pop", None) => ModeChange::Pop,
+                ("priority", _) | ("parse", _) | ("callback", _) | ("mode", _)
+                | ("push", _) | ("switch", _) | ("pop", _) | ("followed_by", _)
+                | ("allow_unreachable", _) =>
+                    return Err(syn::Error::new(
+                        kv.key.span(),
+                        "Invalid parameter value"
+                    )),
                 _ => return Err(syn::Error::new(
                     kv.key.span(),
                     "Invalid parameter name"
                 ))
+            };
+
+            if params.mode_change.is_some() {
+                return Err(syn::Error::new(
+                    kv.key.span(),
+                    "More than one mode change"
+                ))
             }
+            params.mode_change = Some(mode_change);
         }
 
-        Ok(RegexAttr { regex, priority })
+        Ok(params)
     }
 }
 
-impl Parse for TokenAttr {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexAttr {
+    pub regex: Regex,
+    /// The regular expression as written in the attribute
+    pub pattern: String,
+    pub params: RuleParams,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenAttr {
+    pub token: Regex,
+    /// The token string, if it is valid UTF-8
+    pub string: Option<String>,
+    /// The token string, with the invalid UTF-8 bytes escaped
+    pub pattern: String,
+    pub params: RuleParams,
+}
+
+/// The source of the regular expression between two cursors
+///
+/// A single string literal is unquoted, other expressions are kept as is
+fn regex_pattern(begin: Cursor, end: Cursor) -> String {
+    let mut tokens = TokenStream::new();
+    let mut cursor = begin;
+    while cursor != end {
+        let (tt, next) = match cursor.token_tree() {
+            Some(step) => step,
+            None => break,
+        };
+        tokens.extend(Some(tt));
+        cursor = next;
+    }
+
+    match syn::parse2::<LitStr>(tokens.clone()) {
+        Ok(lit) => lit.value(),
+        Err(_) => tokens.to_string(),
+    }
+}
+
+impl RegexAttr {
+    fn parse(input: ParseStream, bytes: bool) -> syn::Result<Self> {
         let content;
         parenthesized!(content in input);
         let input = content;
 
-        let token = input.parse::<LitStr>()?;
-        let token = Regex::literal_str(&token.value());
-
-        if input.is_empty() {
-            return Ok(TokenAttr {
-                token,
-                priority: 0,
-            })
-        }
-        else {
-            input.parse::<Token![,]>()?;
-        }
+        let begin = input.cursor();
+        let regex = parse_regex_expr(&input, bytes)?;
+        let pattern = regex_pattern(begin, input.cursor());
+        let params = RuleParams::parse(&input, bytes)?;
 
-        let mut priority = 0;
+        Ok(RegexAttr { regex, pattern, params })
+    }
+}
 
-        let kvs = input.parse_terminated::<_, Token![,]>(KeyValue::parse)?;
-        for kv in kvs {
-            match &*kv.key.to_string() {
-                "priority" => {
-                    let Value::Int(value) = kv.value;
+impl TokenAttr {
+    fn parse(input: ParseStream, bytes: bool) -> syn::Result<Self> {
+        let content;
+        parenthesized!(content in input);
+        let input = content;
 
-                    priority = value.base10_parse()?;
-                },
-                _ => return Err(syn::Error::new(
-                    kv.key.span(),
-                    "Invalid parameter name"
-                ))
+        let (token, string, pattern) =
+            if input.peek(LitByteStr) {
+                let token = input.parse::<LitByteStr>()?;
+                if !bytes {
+                    return Err(syn::Error::new(
+                        token.span(),
+                        "Byte string tokens require #[sana(bytes)]"
+                    ))
+                }
+
+                let value = token.value();
+                let string = String::from_utf8(value.clone()).ok();
+                let pattern = string.clone()
+                    .unwrap_or_else(|| value.escape_ascii().to_string());
+
+                (Regex::literal_bytes(&value), string, pattern)
             }
-        }
+            else {
+                let value = input.parse::<LitStr>()?.value();
+                let token =
+                    if bytes { Regex::literal_bytes(value.as_bytes()) }
+                    else { Regex::literal_str(&value) };
+
+                (token, Some(value.clone()), value)
+            };
+        let params = RuleParams::parse(&input, bytes)?;
 
-        Ok(TokenAttr { token, priority })
+        Ok(TokenAttr { token, string, pattern, params })
     }
 }
+
+/// Parse the `#[skip(...)]` attribute
+///
+/// It has the same syntax as `#[regex(...)]`
+pub(crate) fn parse_skip_attr(attr: &Attribute, bytes: bool) -> Option<Spanned<RegexAttr>> {
+    if !attr.path.is_ident("skip") { return None }
+
+    let data = (|input: ParseStream| RegexAttr::parse(input, bytes))
+        .parse2(attr.tokens.clone())
+        .map_err(|e| emit_error!(e))
+        .ok()?;
+
+    Some(Spanned { data, span: attr.bracket_token.span })
+}
+
+/// Parse the `#[mode(...)]` attribute
+pub(crate) fn parse_mode_attr(attr: &Attribute) -> Option<Vec<Ident>> {
+    if !attr.path.is_ident("mode") { return None }
+
+    let modes = |input: ParseStream| {
+        let content;
+        parenthesized!(content in input);
+
+        content.parse_terminated::<_, Token![,]>(Ident::parse)
+    };
+
+    modes.parse2(attr.tokens.clone())
+        .map(|modes| modes.into_iter().collect())
+        .map_err(|e| emit_error!(e))
+        .ok()
+}
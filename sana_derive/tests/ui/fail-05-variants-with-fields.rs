@@ -5,6 +5,12 @@ enum Token {
     #[token("token")]
     WithFields(i32),
 
+    #[token("pair", parse = pair)]
+    Pair(i32, i32),
+
+    #[token("named", parse = named)]
+    Named { value: i32 },
+
     #[error]
     Error,
 }
@@ -0,0 +1,26 @@
+use sana_derive::Sana;
+
+#[derive(Clone, Copy, Sana)]
+enum Token {
+    #[regex("[a-z]+", priority = 1)]
+    Ident,
+
+    #[token("in")]
+    In,
+
+    #[regex("[a-z]" & "[0-9]")]
+    Never,
+
+    #[regex("[0-9]+" & "[a-z]+", allow_unreachable)]
+    Allowed,
+
+    // The lint of rustc does not affect the check
+    #[regex("[0-9]" & "[a-z]")]
+    #[allow(dead_code)]
+    DeadCode,
+
+    #[error]
+    Error,
+}
+
+fn main() { }
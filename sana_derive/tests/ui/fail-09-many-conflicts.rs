@@ -0,0 +1,24 @@
+use sana_derive::Sana;
+
+#[derive(Clone, Copy, Sana)]
+enum Token {
+    #[regex("[a-z]+")]
+    Ident,
+
+    #[token("let")]
+    Let,
+
+    #[regex("[0-9]+")]
+    Number,
+
+    #[regex("0x[0-9a-f]+")]
+    Hex,
+
+    #[regex("[0-9][0-9a-fx]*")]
+    Digits,
+
+    #[error]
+    Error,
+}
+
+fn main() { }
@@ -0,0 +1,20 @@
+use sana_derive::Sana;
+
+#[derive(Clone, Copy, Sana)]
+enum Token {
+    #[token("\"", push = string)]
+    Quote,
+
+    #[token("'", switch = char, priority = 1)]
+    #[mode(chars)]
+    Apostrophe,
+
+    #[token("x", pop, push = chars)]
+    X,
+
+    #[error]
+    #[mode(chars)]
+    Error,
+}
+
+fn main() { }
@@ -0,0 +1,19 @@
+use sana_derive::Sana;
+
+#[derive(Clone, Copy, Sana)]
+enum Token {
+    #[regex("[a-z]+", followed_by = " *:")]
+    Label,
+
+    #[regex("[0-9]+", followed_by = "[^.]")]
+    #[regex("0x[0-9a-f]+")]
+    Number,
+
+    #[token("if", followed_by = "x", followed_by = " ")]
+    If,
+
+    #[error]
+    Error,
+}
+
+fn main() { }
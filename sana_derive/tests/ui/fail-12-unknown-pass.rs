@@ -0,0 +1,13 @@
+use sana_derive::Sana;
+
+#[derive(Clone, Copy, Sana)]
+#[sana(no_optimize(dedup_blocks, inline_blocks))]
+enum Token {
+    #[token("x")]
+    X,
+
+    #[error]
+    Error,
+}
+
+fn main() { }